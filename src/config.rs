@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::time::Duration;
+
+use crate::ecobee::client::DEFAULT_BASE_URL as DEFAULT_ECOBEE_BASE_URL;
+use crate::http::RetryPolicy;
+use crate::secrets::SecretBackend;
+use crate::units::TemperatureUnit;
+
+pub static DEFAULT_WEATHER_BASE_URL: &str = "https://api.weatherapi.com";
+pub static ECOBEE_URL_ENV: &str = "ECOBEE_API_URL";
+pub static WEATHER_URL_ENV: &str = "WEATHER_API_URL";
+
+/// # Config
+/// 
+/// General settings, stored in "config.yaml" in the configuration directory. Every field is optional.
+/// 
+/// ecobee_base_url     Base URL of the Ecobee API (overridden by the ECOBEE_API_URL environment variable).
+/// weather_base_url    Base URL of WeatherAPI (overridden by the WEATHER_API_URL environment variable).
+/// retries             How many times to retry a request after a transport error, 429 or 5xx (default 3).
+/// retry_delay_ms      Delay before the first retry in milliseconds, doubled for each one after (default 1000).
+/// max_retry_delay_ms  Longest single wait before a retry in milliseconds, including a server's Retry-After (default 120000).
+///                     A longer Retry-After fails the request instead.
+/// secrets             Where the app key and tokens are kept: "file" (default) or "encrypted_file" (see `secrets::SecretBackend`).
+/// units               "fahrenheit" or "celsius", for every temperature shown or entered (status, holds, and weather thresholds).
+///                     Defaults to the weather settings' `metric`, as older versions used it for holds.
+#[derive(Clone, Default, Deserialize, Debug, Serialize)]
+pub struct Config {
+    pub ecobee_base_url: Option<String>,
+    pub weather_base_url: Option<String>,
+    pub retries: Option<u32>,
+    pub retry_delay_ms: Option<u64>,
+    pub max_retry_delay_ms: Option<u64>,
+    pub secrets: Option<SecretBackend>,
+    pub units: Option<TemperatureUnit>,
+}
+
+impl Config {
+
+    /// # ecobee_base_url(&self) -> String
+    /// 
+    /// The Ecobee API base URL: environment variable, then config file, then the real API.
+    pub fn ecobee_base_url(&self) -> String {
+        resolve(ECOBEE_URL_ENV, &self.ecobee_base_url, DEFAULT_ECOBEE_BASE_URL)
+    }
+
+    /// # weather_base_url(&self) -> String
+    /// 
+    /// The WeatherAPI base URL: environment variable, then config file, then the real API.
+    pub fn weather_base_url(&self) -> String {
+        resolve(WEATHER_URL_ENV, &self.weather_base_url, DEFAULT_WEATHER_BASE_URL)
+    }
+
+    /// # retry_policy(&self) -> RetryPolicy
+    /// 
+    /// The retry policy for API requests, with any configured values in place of the defaults.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        let default = RetryPolicy::default();
+        RetryPolicy {
+            retries: self.retries.unwrap_or(default.retries),
+            base_delay: self.retry_delay_ms.map(Duration::from_millis).unwrap_or(default.base_delay),
+            max_delay: self.max_retry_delay_ms.map(Duration::from_millis).unwrap_or(default.max_delay),
+        }
+    }
+
+    /// # temperature_unit(&self, metric: Option<bool>) -> TemperatureUnit
+    /// 
+    /// The configured unit, or else Celsius if the weather settings' `metric` is true, otherwise Fahrenheit.
+    pub fn temperature_unit(&self, metric: Option<bool>) -> TemperatureUnit {
+        self.units.unwrap_or_else(|| TemperatureUnit::from_metric(metric))
+    }
+}
+
+/// # resolve(env_var: &str, configured: &Option<String>, default: &str) -> String
+/// 
+/// Pick the first non-empty value, without a trailing slash.
+fn resolve(env_var: &str, configured: &Option<String>, default: &str) -> String {
+    env::var(env_var).ok()
+        .filter(|v| !v.is_empty())
+        .or_else(|| configured.clone().filter(|v| !v.is_empty()))
+        .unwrap_or_else(|| default.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
@@ -1,230 +1,483 @@
-use chrono::Local;
+use chrono::{Local, Utc};
 use log::{debug, info, error};
-use std::io;
+use std::io::Read;
+use std::thread;
+use std::time::{Duration, Instant};
 use ureq;
-use ureq::Error;
 
+use crate::ecobee::client::{EcobeeClient, TOKEN_REFRESH_MARGIN};
 use crate::ecobee::models;
-use crate::storage;
+use crate::error::{Error, Result};
+use crate::http::{self, RetryPolicy};
+use crate::prompt;
+use crate::storage::{self, Location};
+use crate::units::TemperatureUnit;
 
-/// # api_key()
+/// # api_key(location: &Location) -> Result<()>
 /// 
 /// Get the API Key from the user, and store it locally for future use.
 /// Create the configuration directory if it doesn't yet exist (silently).
-pub fn api_key() {
+pub fn api_key(location: &Location) -> Result<()> {
     println!("Enter your API Key from the Developer section of the Ecobee consumer portal: ");
-    let mut new_key = String::new();
-    io::stdin().read_line(&mut new_key).unwrap();
-    new_key.truncate(new_key.len() - 1);
-    println!("You entered {new_key}\nProceed? (y/n)");
-    let mut answer = String::new();
-    io::stdin().read_line(&mut answer).unwrap();
-    answer.truncate(answer.len() - 1);
+    let new_key = prompt::read_line("")?;
+    let answer = prompt::read_line(&format!("You entered {new_key}\nProceed? (y/n)\n"))?;
     if answer == "y" {
-        storage::create_config_dir();
-        storage::write_api_key(new_key);
+        set_api_key(location, &new_key)?;
     }
+    Ok(())
 }
 
+/// # api_key_from(location: &Location, reader: impl Read) -> Result<()>
+/// 
+/// Read the API Key from `reader` (eg stdin, for provisioning tools), and store it without asking for confirmation.
+pub fn api_key_from(location: &Location, mut reader: impl Read) -> Result<()> {
+    let mut new_key = String::new();
+    reader.read_to_string(&mut new_key).map_err(|e| Error::Config(format!("Error reading api key: {e}")))?;
+    set_api_key(location, &new_key)
+}
 
-/// # authorize()
+/// # set_api_key(location: &Location, api_key: &str) -> Result<()>
 /// 
-/// https://www.ecobee.com/home/developer/api/documentation/v1/auth/pin-api-authorization.shtml
-///
-/// Authorize if using for the first time to authorize the app in your console.
-pub fn authorize() {
-    println!("Authorizing!");
-    
-    let app_key = storage::load_app_key();
-
-    match ureq::get("https://api.ecobee.com/authorize")
-    .query("response_type", "ecobeePin")
-    .query("client_id", app_key.as_str())
-    .query("scope", "smartWrite").call() {
-        Ok(response) => {
-            match response.into_json::<models::AuthorizeResponse>() {
-                Ok(auth) => {
-                    // Write the auth token.
-                    storage::write_tokens(auth.code, "".to_string());
-                    println!("Ecobee Authorization PIN: {}", auth.ecobee_pin);
-                    println!("(expires in {} minutes)", auth.expires_in);
-                    println!("Log into the Ecobee web portal and register the application using the PIN in your `My Apps` widget.");
-                },
-                Err(e) => error!("{e:?}")
-            };
-        },
-        Err(Error::Status(code, response)) => {
-            error!("Error with request to authorize: {code}\n{}", response.into_string().unwrap());
-        }
-        Err(e) => { error!("Transport error: {e}") }
+/// Store the API Key (trimmed of surrounding whitespace), creating the configuration directory if needed.
+/// An empty key is an error.
+pub fn set_api_key(location: &Location, api_key: &str) -> Result<()> {
+    let api_key = api_key.trim();
+    if api_key.is_empty() {
+        return Err(Error::Config("The api key is empty.".to_string()));
     }
+    storage::create_config_dir(location)?;
+    storage::write_api_key(location, api_key.to_string())
 }
 
-/// # fetch_tokens(access_token: String, grant_type: &str)
-/// 
-/// Call the token API endpoint to retrieve the access and refresh tokens. 
-/// 
-/// https://www.ecobee.com/home/developer/api/documentation/v1/auth/auth-req-resp.shtml
-/// https://www.ecobee.com/home/developer/api/documentation/v1/auth/token-refresh.shtml
-/// 
-/// API params:
-/// 
-/// grant_type  This is always "ecobeePin" for this authorization flow.
-/// code        The authorization code obtained from the /authorize request.
-/// client_id   This is your unique application key.
-/// ecobee_type (Deprecated)
-pub fn fetch_tokens(access_token: String, grant_type: &str) {
-
-    let app_key = storage::load_app_key();
-    let token_param = match grant_type {
-        "ecobeePin" => "code",
-        "refresh_token" => "refresh_token",
-        _ => panic!("Bad grant type for fetch_tokens: {grant_type}")
-    };
-
-    match ureq::post("https://api.ecobee.com/token")
-    .query("grant_type", grant_type)
-    .query("client_id", app_key.as_str())
-    .query(token_param, access_token.as_str()).call() {
-        Ok(response) => {
-            match response.into_json::<models::TokenResponse>() {
-                Ok(tok) => {
-                    storage::write_tokens(tok.access_token, tok.refresh_token);
-                    debug!("Tokens retrieved successfully. Expires in {} minutes", tok.expires_in);
-                },
-                Err(e) => error!("{e:?}")
+impl EcobeeClient {
+
+    /// # authorize(&mut self) -> Result<AuthorizeResponse>
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/auth/pin-api-authorization.shtml
+    ///
+    /// Authorize if using for the first time to authorize the app in your console.
+    /// The returned PIN must be entered in the Ecobee portal before `get_tokens_with_code` is called.
+    pub fn authorize(&mut self) -> Result<models::AuthorizeResponse> {
+        let response = http::call(&self.retry, ureq::get(&self.url("/authorize"))
+        .query("response_type", "ecobeePin")
+        .query("client_id", self.app_key.as_str())
+        .query("scope", "smartWrite"))?;
+        let auth = response.into_json::<models::AuthorizeResponse>()
+            .map_err(|e| Error::Json(e.to_string()))?;
+
+        // Store the authorization code in place of the access token until the PIN is registered.
+        self.set_tokens(models::Tokens { access_token: auth.code.clone(), refresh_token: "".to_string(), issued_at: None, expires_at: None })?;
+        Ok(auth)
+    }
+
+    /// # fetch_tokens(&mut self, access_token: &str, grant_type: &str) -> Result<()>
+    /// 
+    /// Call the token API endpoint to retrieve the access and refresh tokens. 
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/auth/auth-req-resp.shtml
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/auth/token-refresh.shtml
+    /// 
+    /// API params:
+    /// 
+    /// grant_type  This is always "ecobeePin" for this authorization flow.
+    /// code        The authorization code obtained from the /authorize request.
+    /// client_id   This is your unique application key.
+    /// ecobee_type (Deprecated)
+    pub fn fetch_tokens(&mut self, access_token: &str, grant_type: &str) -> Result<()> {
+        let token_param = match grant_type {
+            "ecobeePin" => "code",
+            "refresh_token" => "refresh_token",
+            _ => return Err(Error::Token(format!("Bad grant type for fetch_tokens: {grant_type}")))
+        };
+
+        // Never retried: a refresh that failed in transit may still have rotated the refresh token on Ecobee's side,
+        // and resending the old one would get invalid_grant and force a full re-authorization.
+        let response = http::call(&RetryPolicy::none(), ureq::post(&self.url("/token"))
+        .query("grant_type", grant_type)
+        .query("client_id", self.app_key.as_str())
+        .query(token_param, access_token))?;
+        let tok = response.into_json::<models::TokenResponse>()
+            .map_err(|e| Error::Json(e.to_string()))?;
+        debug!("Tokens retrieved successfully. Expires in {} seconds", tok.expires_in);
+        let issued_at = Utc::now();
+        self.set_tokens(models::Tokens {
+            access_token: tok.access_token,
+            refresh_token: tok.refresh_token,
+            issued_at: Some(issued_at),
+            expires_at: Some(issued_at + chrono::Duration::seconds(tok.expires_in.into()))
+        })
+    }
+
+    /// # wait_for_authorization(&mut self, auth: &AuthorizeResponse) -> Result<()>
+    /// 
+    /// Poll the token endpoint with the authorization code until the user registers the app with the PIN, then store the tokens.
+    /// 
+    /// Polls every `auth.interval` seconds, doubling the interval whenever the server answers "slow_down",
+    /// and keeping on while it answers "authorization_pending". Gives up with a Token error once the PIN expires
+    /// (`auth.expires_in` minutes). Any other error is returned straight away.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/auth/pin-api-authorization.shtml
+    pub fn wait_for_authorization(&mut self, auth: &models::AuthorizeResponse) -> Result<()> {
+        let deadline = Instant::now() + Duration::from_secs(u64::from(auth.expires_in) * 60);
+        let mut interval = Duration::from_secs(auth.interval.into());
+        loop {
+            let now = Instant::now();
+            if now + interval >= deadline {
+                return Err(Error::Token("The PIN expired before the app was registered. Run --setup again.".to_string()));
+            }
+            thread::sleep(interval);
+            match self.fetch_tokens(&auth.code, "ecobeePin") {
+                Ok(()) => return Ok(()),
+                Err(e) => match e.oauth_error().as_deref() {
+                    Some("authorization_pending") => debug!("Waiting for the app to be registered."),
+                    Some("slow_down") => {
+                        interval *= 2;
+                        debug!("Asked to slow down, polling every {interval:?}.");
+                    },
+                    _ => return Err(e)
+                }
             }
-        },
-        Err(Error::Status(code, response)) => {
-            error!("Error with request for tokens: {code}\n{}", response.into_string().unwrap());
         }
-        Err(e) => { error!("Transport error: {e}") }
     }
 
-}
+    /// # get_tokens_with_code(&mut self) -> Result<()>
+    /// 
+    /// Call fetch_token with the code from the authorization call, and a `grant_type` of "ecobeePin" to get the initial access and refresh tokens.
+    /// 
+    /// This needs to be called soon after calling authorize().
+    pub fn get_tokens_with_code(&mut self) -> Result<()> {
+        let code = self.tokens()?.access_token.clone();
 
-/// # get_tokens_with_code()
-/// 
-/// Call fetch_token with the code from the authorization call, and a `grant_type` of "ecobeePin" to get the initial access and refresh tokens.
-/// 
-/// This needs to be called soon after calling authorize().
-pub fn get_tokens_with_code() {
-    let tokens = storage::load_tokens();
+        info!("Requesting access tokens after registering app with PIN");
 
-    info!("Requesting access tokens after registering app with PIN");
+        self.fetch_tokens(&code, "ecobeePin")
+    }
 
-    fetch_tokens(tokens.access_token, "ecobeePin");
-}
+    /// # refresh_tokens(&mut self) -> Result<()>
+    /// 
+    /// Call fetch_token with the refresh token and a `grant_type` of "refresh_token", to refresh the tokens and update the store.
+    /// 
+    /// The store is locked throughout, and re-read once the lock is held. If another process refreshed in the meantime
+    /// (so the refresh token we had is now spent), its still valid tokens are used instead of refreshing again.
+    pub fn refresh_tokens(&mut self) -> Result<()> {
+        let _lock = self.lock_tokens()?;
+        let previous = self.tokens()?.refresh_token.clone();
+        let stored = self.reload_tokens()?;
+        if stored.refresh_token != previous && !stored.expires_within(TOKEN_REFRESH_MARGIN) {
+            debug!("Tokens were refreshed by another process.");
+            return Ok(());
+        }
+        let refresh_token = stored.refresh_token.clone();
+        if refresh_token.is_empty() {
+            return Err(Error::Token("No refresh token stored. Run --auth to complete setup.".to_string()));
+        }
 
-/// # refresh_tokens()
-/// 
-/// Call fetch_token with the refresh token and a `grant_type` of "refresh_token", to refresh the tokens and update the local store.
-pub fn refresh_tokens() {
-    let tokens = storage::load_tokens();
+        debug!("Refreshing tokens.");
 
-    debug!("Refreshing tokens.");
+        self.fetch_tokens(&refresh_token, "refresh_token")
+    }
 
-    fetch_tokens(tokens.refresh_token, "refresh_token");
-}
+    /// # valid_access_token(&mut self) -> Result<String>
+    /// 
+    /// The current access token, refreshed first if it expires within `TOKEN_REFRESH_MARGIN` (or its expiry is unknown).
+    pub fn valid_access_token(&mut self) -> Result<String> {
+        if self.tokens()?.expires_within(TOKEN_REFRESH_MARGIN) {
+            self.refresh_tokens()?;
+        }
+        Ok(self.tokens()?.access_token.clone())
+    }
+
+    /// # authorized<T>(&mut self, call: impl FnMut(&EcobeeClient, &str) -> Result<T>) -> Result<T>
+    /// 
+    /// Make an API call with a valid access token, passing `call` the Authorization header value.
+    /// 
+    /// If Ecobee still reports the token as expired (status 14), eg because it was revoked or the clock is off,
+    /// refresh the tokens and make the call once more.
+    pub(crate) fn authorized<T>(&mut self, mut call: impl FnMut(&EcobeeClient, &str) -> Result<T>) -> Result<T> {
+        let access = self.valid_access_token()?;
+        match call(self, &format!("Bearer {access}")) {
+            Err(e) if e.is_token_expired() => {
+                info!("Access token expired, refreshing and retrying.");
+                self.refresh_tokens()?;
+                let access = self.tokens()?.access_token.clone();
+                call(self, &format!("Bearer {access}"))
+            },
+            result => result
+        }
+    }
 
+    /// # thermostat_status(&mut self) -> Result<ThermostatStatus>
+    /// 
+    /// For every registered thermostat, get the name, identifier, HVAC Mode, Actual Temperature, Actual Humidity,
+    /// the equipment running, any events (eg holds), every sensor's readings and any alerts,
+    /// along with whether they all share the same HVAC Mode.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/operations/get-thermostats.shtml
+    pub fn thermostat_status(&mut self) -> Result<models::ThermostatStatus> {
+        let thermostats = self.thermostats(serde_json::json!({
+            "includeAlerts": "true",
+            "selectionType": "registered",
+            "selectionMatch": "",
+            "includeEvents": "true",
+            "includeSettings": "true",
+            "includeRuntime": "true",
+            "includeEquipmentStatus": "true",
+            "includeSensors": "true"
+        }))?;
+        Ok(models::ThermostatStatus { hvac_mode: models::HvacModeStatus::of(&thermostats), thermostats })
+    }
 
-/// # thermostat_status()
-/// 
-/// For every registered thermostat, get the name, identifier, HVAC Mode, Actual Temperature, and Actual Humidity.
-/// Refresh a local store with thermostat identifiers and names for use with the update function.
-/// 
-/// https://www.ecobee.com/home/developer/api/documentation/v1/operations/get-thermostats.shtml
-pub fn thermostat_status() -> String {
-    let tokens = storage::load_tokens();
-    let access = format!("Bearer {}", tokens.access_token.as_str());
-    let mut current_mode = String::new();
-    match ureq::get("https://api.ecobee.com/1/thermostat")
-    .set("Content-Type", "application/json;charset=UTF-8")
-    .set("Authorization", access.as_str())    
-    .query("json", "{\"selection\":{\"includeAlertsv\":\"true\",\"selectionType\":\"registered\",\"selectionMatch\":\"\",\"includeEvents\":\"true\",\"includeSettings\":\"true\",\"includeRuntime\":\"true\"}}").call() {
-        Ok(response) => {
-            match response.into_json::<models::StatusResponse>() {
-                Ok(resp) => {
-                    println!("\nThermostats\n");
-                    let mut thermostats_meta_vec: Vec<models::ThermostatMeta> = vec![];
-                    for thermostat in resp.thermostats {
-                        let thermostat_meta = models::ThermostatMeta {
-                            identifier: thermostat.identifier,
-                            name: thermostat.name
-                        };
-                        thermostats_meta_vec.push(thermostat_meta.clone());
-                        println!("Thermostat {} (id: {})", thermostat_meta.name, thermostat_meta.identifier);
-                        println!("HVAC Mode: {}", thermostat.settings.hvac_mode);
-                        let mode = thermostat.settings.hvac_mode;
-                        if !current_mode.is_empty() && current_mode != mode {
-                            current_mode = "inconsistent".to_string();
-                        } else {
-                            current_mode = mode;
-                        }
-                        let temp = thermostat.runtime.actual_temperature / 10.0;
-                        let desired_cool = thermostat.runtime.desired_cool / 10.0;
-                        let desired_heat = thermostat.runtime.desired_heat / 10.0;
-                        println!("Actual Temperature: {}, Actual Humidity: {}%", temp, thermostat.runtime.actual_humidity);
-                        println!("Desired Cool: {}, Desired Heat: {}\n", desired_cool, desired_heat);
-                    }
-                    storage::write_thermostats(thermostats_meta_vec);
+    /// # thermostats(&mut self, selection: serde_json::Value) -> Result<Vec<StatusResponseThermostat>>
+    /// 
+    /// Get the thermostats matching `selection`, following every page of the response and merging them in order.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/operations/get-thermostats.shtml
+    pub(crate) fn thermostats(&mut self, selection: serde_json::Value) -> Result<Vec<models::StatusResponseThermostat>> {
+        let mut thermostats = vec![];
+        let mut page = 1;
+        loop {
+            let query = serde_json::json!({ "selection": selection, "page": { "page": page } });
+            let resp = self.authorized(|client, access| {
+                http::call(&client.retry, ureq::get(&client.url("/1/thermostat"))
+                .set("Content-Type", "application/json;charset=UTF-8")
+                .set("Authorization", access)
+                .query("json", &query.to_string()))?
+                .into_json::<models::StatusResponse>()
+                .map_err(|e| Error::Json(e.to_string()))
+            })?;
+            thermostats.extend(resp.thermostats);
+            match resp.page {
+                Some(p) if page < p.total_pages => {
+                    debug!("Fetched page {page} of {} of thermostats.", p.total_pages);
+                    page += 1;
                 },
-                Err(e) => error!("{e:?}")
-            };
-        },
-        Err(Error::Status(code, response)) => {
-            error!("Error with request for thermostats: {}\n{}", code, response.into_string().unwrap());
+                _ => return Ok(thermostats)
+            }
         }
-        Err(e) => { error!("Transport error: {e}") }
     }
-    current_mode.replace('"', "")
-}
 
+    /// # alerts(&mut self) -> Result<Vec<ThermostatAlert>>
+    /// 
+    /// Every alert waiting to be acknowledged, across all registered thermostats (from a status call).
+    pub fn alerts(&mut self) -> Result<Vec<models::ThermostatAlert>> {
+        let status = self.thermostat_status()?;
+        Ok(status.thermostats.into_iter()
+            .flat_map(|t| {
+                let meta = t.meta();
+                t.alerts.into_iter().map(move |alert| models::ThermostatAlert { thermostat: meta.clone(), alert })
+            })
+            .collect())
+    }
 
-/// # update_thermostats(mode: &str)
-/// 
-/// For every registered thermostat, set the HVAC Mode to the provided string `mode`.
-/// 
-/// The API technically allows doing this in a single call, but in practice that often fails for some of the thermostats.
-/// (Leaving them reporting one state over wifi, and the actual state on the device itself).
-/// Breaking it into one call per thermostat bypasses this issue.
-/// 
-/// Note: Depending on the frequency with which this is called and number of thermostats - be wary of too many calls to the API.
-/// 
-/// https://www.ecobee.com/home/developer/api/documentation/v1/operations/post-update-thermostats.shtml
-pub fn update_thermostats(mode: &str) {
-    let tokens = storage::load_tokens();
-    let access = format!("Bearer {}", tokens.access_token.as_str());
-    let thermostats = storage::load_thermostats();
-    for thermostat in thermostats {
-        println!("Updating {} to {mode} @ {}", thermostat.name, Local::now().to_rfc2822());
-        match ureq::post("https://api.ecobee.com/1/thermostat")
-        .set("Content-Type", "application/json;charset=UTF-8")
-        .set("Authorization", access.as_str())    
-        .query("format", "json").send_json(ureq::json!({
+    /// # vacations(&mut self) -> Result<Vec<ThermostatVacation>>
+    /// 
+    /// Every vacation event, running or scheduled, across all registered thermostats (from a status call).
+    pub fn vacations(&mut self) -> Result<Vec<models::ThermostatVacation>> {
+        let status = self.thermostat_status()?;
+        Ok(status.thermostats.iter()
+            .flat_map(|t| t.vacations().into_iter().map(|vacation| models::ThermostatVacation { thermostat: t.meta(), vacation: vacation.clone() }))
+            .collect())
+    }
+
+    /// # thermostat_details(&mut self, thermostats: &[ThermostatMeta], include_sensors: bool) -> Result<Vec<StatusResponseThermostat>>
+    /// 
+    /// Get the settings and runtime of the given thermostats, and their sensor readings if `include_sensors`.
+    /// For use once a summary shows something has changed, rather than for polling.
+    pub fn thermostat_details(&mut self, thermostats: &[models::ThermostatMeta], include_sensors: bool) -> Result<Vec<models::StatusResponseThermostat>> {
+        let identifiers: Vec<&str> = thermostats.iter().map(|t| t.identifier.as_str()).collect();
+        self.thermostats(serde_json::json!({
+            "selectionType": "thermostats",
+            "selectionMatch": identifiers.join(","),
+            "includeSettings": true,
+            "includeRuntime": true,
+            "includeSensors": include_sensors
+        }))
+    }
+
+    /// # thermostat_summary(&mut self) -> Result<ThermostatSummary>
+    /// 
+    /// For every registered thermostat, get the revisions and the equipment currently running.
+    /// Unlike the status call this is meant for polling: compare revisions (see `ThermostatSummary::changed_since`)
+    /// and only ask for full details when they change.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/operations/get-thermostat-summary.shtml
+    pub fn thermostat_summary(&mut self) -> Result<models::ThermostatSummary> {
+        let query = serde_json::json!({
             "selection": {
-                "selectionType": "thermostats",
-                "selectionMatch": thermostat.identifier,
-            },
+                "selectionType": "registered",
+                "selectionMatch": "",
+                "includeEquipmentStatus": true
+            }
+        });
+        let resp = self.authorized(|client, access| {
+            http::call(&client.retry, ureq::get(&client.url("/1/thermostatSummary"))
+            .set("Content-Type", "application/json;charset=UTF-8")
+            .set("Authorization", access)
+            .query("json", &query.to_string()))?
+            .into_json::<models::SummaryResponse>()
+            .map_err(|e| Error::Json(e.to_string()))
+        })?;
+        resp.try_into()
+    }
+
+    /// # update_thermostats(&mut self, thermostats: &[ThermostatMeta], mode: HvacMode) -> Result<UpdateReport>
+    /// 
+    /// For every thermostat given, set the HVAC Mode to `mode`.
+    /// 
+    /// The API technically allows doing this in a single call, but in practice that often fails for some of the thermostats.
+    /// (Leaving them reporting one state over wifi, and the actual state on the device itself).
+    /// Breaking it into one call per thermostat bypasses this issue.
+    /// 
+    /// A failure for one thermostat doesn't stop the others from being updated. The report gives the result for each.
+    /// 
+    /// Note: Depending on the frequency with which this is called and number of thermostats - be wary of too many calls to the API.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/operations/post-update-thermostats.shtml
+    pub fn update_thermostats(&mut self, thermostats: &[models::ThermostatMeta], mode: models::HvacMode) -> Result<models::UpdateReport> {
+        self.update_each(thermostats, mode.as_str(), true, ureq::json!({
             "thermostat": {
                 "settings": {
                     "hvacMode": mode
                 }
             }
-        })) {
-            Ok(response) => {
-                match response.into_string() {
-                    Ok(resp) => {
-                        debug!("{resp}");
-                    },
-                    Err(e) => error!("{e:?}")
-                };
-            },
-            Err(Error::Status(code, response)) => {
-                error!("Error with request for thermostats: {}\n{}", code, response.into_string().unwrap());
-            }
-            Err(e) => { error!("Transport error: {e}") }
+        }))
+    }
+
+    /// # set_hold(&mut self, thermostats: &[ThermostatMeta], hold: &Hold, unit: TemperatureUnit) -> Result<UpdateReport>
+    /// 
+    /// For every thermostat given, hold the heat and cool setpoints, using one call per thermostat (see `update_thermostats`).
+    /// The setpoints are described in `unit` in the report.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/functions/SetHold.shtml
+    pub fn set_hold(&mut self, thermostats: &[models::ThermostatMeta], hold: &models::Hold, unit: TemperatureUnit) -> Result<models::UpdateReport> {
+        let description = format!("hold heat {}{symbol} / cool {}{symbol}",
+            unit.from_ecobee(hold.heat_hold_temp as f64), unit.from_ecobee(hold.cool_hold_temp as f64), symbol = unit.symbol());
+        self.update_each(thermostats, &description, true, ureq::json!({
+            "functions": [{
+                "type": "setHold",
+                "params": hold.params()
+            }]
+        }))
+    }
+
+    /// # resume_program(&mut self, thermostats: &[ThermostatMeta], resume_all: bool) -> Result<UpdateReport>
+    /// 
+    /// For every thermostat given, return to the program (schedule), using one call per thermostat (see `update_thermostats`).
+    /// With `resume_all` every hold on the event stack is removed, otherwise only the most recent one
+    /// (so only the former is retried, as resending the latter could remove a second hold).
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/functions/ResumeProgram.shtml
+    pub fn resume_program(&mut self, thermostats: &[models::ThermostatMeta], resume_all: bool) -> Result<models::UpdateReport> {
+        self.update_each(thermostats, "resume program", resume_all, ureq::json!({
+            "functions": [{
+                "type": "resumeProgram",
+                "params": {
+                    "resumeAll": resume_all
+                }
+            }]
+        }))
+    }
+
+    /// # create_vacation(&mut self, thermostats: &[ThermostatMeta], vacation: &Vacation) -> Result<UpdateReport>
+    /// 
+    /// For every thermostat given, create the same vacation, using one call per thermostat (see `update_thermostats`).
+    /// Never retried: a vacation created by a request whose response was lost would be created twice.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/functions/CreateVacation.shtml
+    pub fn create_vacation(&mut self, thermostats: &[models::ThermostatMeta], vacation: &models::Vacation) -> Result<models::UpdateReport> {
+        let description = format!("vacation {} ({} to {})", vacation.name, vacation.start.format("%Y-%m-%d %H:%M"), vacation.end.format("%Y-%m-%d %H:%M"));
+        self.update_each(thermostats, &description, false, ureq::json!({
+            "functions": [{
+                "type": "createVacation",
+                "params": vacation.params()
+            }]
+        }))
+    }
+
+    /// # delete_vacation(&mut self, thermostats: &[ThermostatMeta], name: &str) -> Result<UpdateReport>
+    /// 
+    /// For every thermostat given, delete the vacation with this name, using one call per thermostat (see `update_thermostats`).
+    /// Never retried: resending a delete that was applied would fail, as the vacation is gone.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/functions/DeleteVacation.shtml
+    pub fn delete_vacation(&mut self, thermostats: &[models::ThermostatMeta], name: &str) -> Result<models::UpdateReport> {
+        self.update_each(thermostats, &format!("delete vacation {name}"), false, ureq::json!({
+            "functions": [{
+                "type": "deleteVacation",
+                "params": {
+                    "name": name
+                }
+            }]
+        }))
+    }
+
+    /// # acknowledge_alert(&mut self, thermostat: &ThermostatMeta, acknowledge_ref: &str) -> Result<UpdateReport>
+    /// 
+    /// Accept (dismiss) one of the thermostat's alerts, by its acknowledge reference.
+    /// Never retried: once applied the reference no longer matches, so a resent acknowledge would be reported as failed.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/functions/Acknowledge.shtml
+    pub fn acknowledge_alert(&mut self, thermostat: &models::ThermostatMeta, acknowledge_ref: &str) -> Result<models::UpdateReport> {
+        self.update_each(std::slice::from_ref(thermostat), &format!("acknowledge alert {acknowledge_ref}"), false, ureq::json!({
+            "functions": [{
+                "type": "acknowledge",
+                "params": {
+                    "thermostatIdentifier": thermostat.identifier,
+                    "ackRef": acknowledge_ref,
+                    "ackType": "accept",
+                    "remindMeLater": false
+                }
+            }]
+        }))
+    }
+
+    /// # progress(&self, message: String)
+    /// 
+    /// Print a progress message, or log it if the client is quiet.
+    pub(crate) fn progress(&self, message: String) {
+        if self.quiet {
+            info!("{message}");
+        } else {
+            println!("{message}");
         }
     }
 
-}
\ No newline at end of file
+    /// # update_each(&mut self, thermostats: &[ThermostatMeta], description: &str, idempotent: bool, body: serde_json::Value) -> Result<UpdateReport>
+    /// 
+    /// POST `body` to the update endpoint once per thermostat, with a selection matching just that thermostat.
+    /// A thermostat only counts as updated if the response `status.code` is 0, since failures can be reported inside a 200.
+    /// A failure for one thermostat doesn't stop the others.
+    /// 
+    /// Failed requests are only retried (with the client's policy) if `idempotent`: a request whose response was lost
+    /// may still have been applied, so only updates that can safely be applied twice may be resent.
+    fn update_each(&mut self, thermostats: &[models::ThermostatMeta], description: &str, idempotent: bool, body: serde_json::Value) -> Result<models::UpdateReport> {
+        let mut results = vec![];
+        for thermostat in thermostats {
+            self.progress(format!("Updating {} to {description} @ {}", thermostat.name, Local::now().to_rfc2822()));
+            let mut request = body.clone();
+            request["selection"] = ureq::json!({
+                "selectionType": "thermostats",
+                "selectionMatch": thermostat.identifier,
+            });
+            let result = self.authorized(|client, access| {
+                let retry = if idempotent { client.retry } else { RetryPolicy::none() };
+                http::send_json(&retry, ureq::post(&client.url("/1/thermostat"))
+                .set("Content-Type", "application/json;charset=UTF-8")
+                .set("Authorization", access)    
+                .query("format", "json"), request.clone())
+                .and_then(|response| response.into_string().map_err(|e| Error::Json(e.to_string())))
+                .and_then(|resp| {
+                    debug!("{resp}");
+                    serde_json::from_str::<models::UpdateResponse>(&resp).map_err(|e| Error::Json(e.to_string()))
+                })
+                .and_then(|resp| resp.into_result())
+            });
+            match &result {
+                Ok(()) => self.progress(format!("Updated {}", thermostat.name)),
+                Err(e) => error!("Error updating {}: {e}", thermostat.name)
+            }
+            results.push(models::UpdateResult { thermostat: thermostat.clone(), result });
+        }
+        Ok(models::UpdateReport { description: description.to_string(), results })
+    }
+}
@@ -0,0 +1,130 @@
+use std::any::Any;
+
+use crate::ecobee::models::Tokens;
+use crate::error::Result;
+use crate::http::RetryPolicy;
+
+/// # TokenStore
+/// 
+/// Persistence for the access and refresh tokens used by `EcobeeClient`.
+/// 
+/// Ecobee rotates the refresh token on every refresh, so whatever `save` receives must be kept,
+/// or the next refresh will fail and the app will have to be authorized again.
+/// 
+/// `lock` is held for the whole of a refresh (reload, request, save), so that stores shared between processes
+/// can make concurrent refreshes take turns. It returns a guard that releases the lock when dropped.
+/// The default does no locking, which is fine for a store used by a single client.
+pub trait TokenStore {
+    fn load(&self) -> Result<Tokens>;
+    fn save(&self, tokens: &Tokens) -> Result<()>;
+    fn lock(&self) -> Result<Box<dyn Any>> {
+        Ok(Box::new(()))
+    }
+}
+
+pub static DEFAULT_BASE_URL: &str = "https://api.ecobee.com";
+
+/// Refresh the access token when it expires within this long, rather than risk it expiring mid-run.
+pub const TOKEN_REFRESH_MARGIN: chrono::Duration = chrono::Duration::minutes(5);
+
+/// # EcobeeClient
+/// 
+/// Owns everything needed to talk to the Ecobee API: the app key, the current tokens, the base URL and the retry policy.
+/// 
+/// Tokens are loaded from the `TokenStore` the first time they are needed, and written back to it whenever they change.
+/// The API calls themselves are implemented in `ecobee::api`.
+pub struct EcobeeClient {
+    pub(crate) app_key: String,
+    pub(crate) base_url: String,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) quiet: bool,
+    tokens: Option<Tokens>,
+    store: Box<dyn TokenStore>,
+}
+
+impl EcobeeClient {
+
+    /// # new(app_key: String, store: Box<dyn TokenStore>) -> EcobeeClient
+    /// 
+    /// Create a client for the given app key, persisting tokens through `store`.
+    pub fn new(app_key: String, store: Box<dyn TokenStore>) -> Self {
+        EcobeeClient {
+            app_key: app_key.trim().to_string(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            retry: RetryPolicy::default(),
+            quiet: false,
+            tokens: None,
+            store,
+        }
+    }
+
+    /// # with_base_url(self, base_url: &str) -> EcobeeClient
+    /// 
+    /// Point the client at a different API host (no trailing slash needed).
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// # with_retry_policy(self, retry: RetryPolicy) -> EcobeeClient
+    /// 
+    /// Retry failed requests according to `retry` instead of the default policy.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// # quiet(self, quiet: bool) -> EcobeeClient
+    /// 
+    /// Log progress (eg each thermostat being updated) at info level instead of printing it, leaving stdout to the caller.
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// # base_url(&self) -> &str
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// # tokens(&mut self) -> Result<&Tokens>
+    /// 
+    /// The current tokens, loaded from the store on first use.
+    pub fn tokens(&mut self) -> Result<&Tokens> {
+        if self.tokens.is_none() {
+            self.tokens = Some(self.store.load()?);
+        }
+        Ok(self.tokens.as_ref().expect("tokens were just loaded"))
+    }
+
+    /// # set_tokens(&mut self, tokens: Tokens) -> Result<()>
+    /// 
+    /// Replace the current tokens and persist them.
+    pub fn set_tokens(&mut self, tokens: Tokens) -> Result<()> {
+        self.store.save(&tokens)?;
+        self.tokens = Some(tokens);
+        Ok(())
+    }
+
+    /// # lock_tokens(&self) -> Result<Box<dyn Any>>
+    /// 
+    /// Take the store's lock (see `TokenStore`), released when the returned guard is dropped.
+    pub(crate) fn lock_tokens(&self) -> Result<Box<dyn Any>> {
+        self.store.lock()
+    }
+
+    /// # reload_tokens(&mut self) -> Result<&Tokens>
+    /// 
+    /// Replace the current tokens with those in the store, in case another process has refreshed them.
+    pub(crate) fn reload_tokens(&mut self) -> Result<&Tokens> {
+        self.tokens = Some(self.store.load()?);
+        Ok(self.tokens.as_ref().expect("tokens were just loaded"))
+    }
+
+    /// # url(&self, path: &str) -> String
+    /// 
+    /// Absolute URL for an API path (eg "/1/thermostat").
+    pub(crate) fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
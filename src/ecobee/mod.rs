@@ -1,2 +1,4 @@
 pub mod api;
-pub mod models;
\ No newline at end of file
+pub mod client;
+pub mod models;
+pub mod verify;
\ No newline at end of file
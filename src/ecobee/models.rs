@@ -1,4 +1,127 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+use crate::units::TemperatureUnit;
+
+/// # HvacMode
+/// 
+/// The thermostat `hvacMode` setting, serialized as the API strings:
+/// "auto", "auxHeatOnly", "cool", "heat", and "off".
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all="camelCase")]
+pub enum HvacMode {
+    Auto,
+    AuxHeatOnly,
+    Cool,
+    Heat,
+    Off,
+}
+
+impl HvacMode {
+
+    /// # as_str(&self) -> &'static str
+    /// 
+    /// The API string for this mode.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HvacMode::Auto => "auto",
+            HvacMode::AuxHeatOnly => "auxHeatOnly",
+            HvacMode::Cool => "cool",
+            HvacMode::Heat => "heat",
+            HvacMode::Off => "off",
+        }
+    }
+}
+
+impl fmt::Display for HvacMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for HvacMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(HvacMode::Auto),
+            "auxHeatOnly" => Ok(HvacMode::AuxHeatOnly),
+            "cool" => Ok(HvacMode::Cool),
+            "heat" => Ok(HvacMode::Heat),
+            "off" => Ok(HvacMode::Off),
+            _ => Err(Error::Config(format!("Unknown hvac mode: {s}")))
+        }
+    }
+}
+
+/// # HvacModeStatus
+/// 
+/// The HVAC mode across a set of thermostats.
+/// 
+/// Uniform  Every thermostat is in the same mode.
+/// Mixed    The thermostats disagree (or there are none), so the mode is given per thermostat identifier
+///          (names needn't be unique, so they are only kept for display).
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub enum HvacModeStatus {
+    Uniform(HvacMode),
+    Mixed(BTreeMap<String, ThermostatMode>),
+}
+
+/// # ThermostatMode
+/// 
+/// The HVAC mode of one thermostat, with its name for display.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct ThermostatMode {
+    pub name: String,
+    pub mode: HvacMode,
+}
+
+impl HvacModeStatus {
+
+    /// # of(thermostats: &[StatusResponseThermostat]) -> HvacModeStatus
+    pub fn of(thermostats: &[StatusResponseThermostat]) -> Self {
+        Self::from_modes(thermostats.iter()
+            .map(|t| (t.identifier.clone(), ThermostatMode { name: t.name.clone(), mode: t.settings.hvac_mode }))
+            .collect())
+    }
+
+    /// # from_modes(modes: BTreeMap<String, ThermostatMode>) -> HvacModeStatus
+    /// 
+    /// From the mode of each thermostat, keyed by identifier.
+    pub fn from_modes(modes: BTreeMap<String, ThermostatMode>) -> Self {
+        let first = modes.values().next().map(|t| t.mode);
+        match first {
+            Some(mode) if modes.values().all(|t| t.mode == mode) => HvacModeStatus::Uniform(mode),
+            _ => HvacModeStatus::Mixed(modes)
+        }
+    }
+
+    /// # uniform(&self) -> Option<HvacMode>
+    /// 
+    /// The shared mode, if there is one.
+    pub fn uniform(&self) -> Option<HvacMode> {
+        match self {
+            HvacModeStatus::Uniform(mode) => Some(*mode),
+            HvacModeStatus::Mixed(_) => None
+        }
+    }
+}
+
+impl fmt::Display for HvacModeStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HvacModeStatus::Uniform(mode) => write!(f, "{mode}"),
+            HvacModeStatus::Mixed(modes) => {
+                let modes: Vec<String> = modes.values().map(|t| format!("{}: {}", t.name, t.mode)).collect();
+                write!(f, "inconsistent ({})", modes.join(", "))
+            }
+        }
+    }
+}
 
 /// # AuthorizeResponse
 /// 
@@ -18,17 +141,156 @@ pub struct AuthorizeResponse {
 }
 
 
+/// # HoldType
+/// 
+/// How long a hold lasts.
+/// 
+/// NextTransition  Until the next program (schedule) change.
+/// Indefinite      Until it is removed.
+/// HoldHours       For the given number of hours.
+/// DateTime        Until the given date and time (thermostat local time).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HoldType {
+    NextTransition,
+    Indefinite,
+    HoldHours(u32),
+    DateTime(NaiveDateTime),
+}
+
+/// # Hold
+/// 
+/// Parameters for the `setHold` function. Temperatures are in Ecobee units (tenths of a degree Fahrenheit).
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/functions/SetHold.shtml
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hold {
+    pub heat_hold_temp: i64,
+    pub cool_hold_temp: i64,
+    pub hold_type: HoldType,
+}
+
+impl Hold {
+
+    /// # params(&self) -> serde_json::Value
+    /// 
+    /// The `params` object for the setHold function.
+    pub fn params(&self) -> serde_json::Value {
+        let mut params = serde_json::json!({
+            "heatHoldTemp": self.heat_hold_temp,
+            "coolHoldTemp": self.cool_hold_temp,
+        });
+        match self.hold_type {
+            HoldType::NextTransition => params["holdType"] = "nextTransition".into(),
+            HoldType::Indefinite => params["holdType"] = "indefinite".into(),
+            HoldType::HoldHours(hours) => {
+                params["holdType"] = "holdHours".into();
+                params["holdHours"] = hours.into();
+            },
+            HoldType::DateTime(end) => {
+                params["holdType"] = "dateTime".into();
+                params["endDate"] = end.format("%Y-%m-%d").to_string().into();
+                params["endTime"] = end.format("%H:%M:%S").to_string().into();
+            },
+        }
+        params
+    }
+}
+
+/// # FanMode
+/// 
+/// The fan setting for a vacation: "auto" (run with heating and cooling) or "on" (run continuously).
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum FanMode {
+    #[default]
+    Auto,
+    On,
+}
+
+impl FromStr for FanMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(FanMode::Auto),
+            "on" => Ok(FanMode::On),
+            _ => Err(format!("unknown fan mode \"{s}\" (expected auto or on)"))
+        }
+    }
+}
+
+impl fmt::Display for FanMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FanMode::Auto => write!(f, "auto"),
+            FanMode::On => write!(f, "on"),
+        }
+    }
+}
+
+/// # Vacation
+/// 
+/// Parameters for the `createVacation` function. Temperatures are in Ecobee units (tenths of a degree Fahrenheit),
+/// and the start and end are in thermostat local time.
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/functions/CreateVacation.shtml
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vacation {
+    pub name: String,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub heat_hold_temp: i64,
+    pub cool_hold_temp: i64,
+    pub fan: FanMode,
+}
+
+impl Vacation {
+
+    /// # params(&self) -> serde_json::Value
+    /// 
+    /// The `params` object for the createVacation function.
+    pub fn params(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "coolHoldTemp": self.cool_hold_temp,
+            "heatHoldTemp": self.heat_hold_temp,
+            "startDate": self.start.format("%Y-%m-%d").to_string(),
+            "startTime": self.start.format("%H:%M:%S").to_string(),
+            "endDate": self.end.format("%Y-%m-%d").to_string(),
+            "endTime": self.end.format("%H:%M:%S").to_string(),
+            "fan": self.fan,
+        })
+    }
+}
+
 /// # StatusResponse
 /// 
-/// ecobeePin   The PIN a user enters in the web portal.
-/// expires_in  The number of minutes until the PIN expires. Ensure you inform the user how much time they have.
-/// code        The authorization token needed to request the access and refresh tokens.
-/// scope       The requested Scope from the original request. This must match the original request.
-/// interval    The minimum amount of seconds which must pass between polling attempts for a token. */
+/// thermostats  The thermostats on this page of the response.
+/// page         Which page this is, and how many there are. Missing from some responses, in which case there is only the one.
 #[derive(Deserialize, Debug)]
 pub struct StatusResponse {
     #[serde(rename="thermostatList")]
     pub thermostats: Vec<StatusResponseThermostat>,
+    #[serde(default)]
+    pub page: Option<Page>,
+}
+
+/// # Page
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/objects/Page.shtml
+/// 
+/// page         The page returned, counting from 1.
+/// total_pages  The number of pages in the full result.
+/// page_size    The number of results per page.
+/// total        The number of results across all pages.
+#[derive(Clone, Copy, Deserialize, Debug)]
+pub struct Page {
+    pub page: u32,
+    #[serde(rename="totalPages")]
+    pub total_pages: u32,
+    #[serde(rename="pageSize")]
+    pub page_size: u32,
+    pub total: u32,
 }
 
 #[derive(Deserialize, Debug)]
@@ -46,15 +308,370 @@ pub struct StatusResponseThermostatRuntime {
 #[derive(Deserialize, Debug)]
 pub struct StatusResponseThermostatSettings {
     #[serde(rename="hvacMode")]
-    pub hvac_mode: String
+    pub hvac_mode: HvacMode
+}
+
+/// # StatusResponseThermostatEvent
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/objects/Event.shtml
+/// 
+/// event_type      "hold", "vacation", "demandResponse", "autoAway", etc.
+/// name            The event name ("auto" for holds set through the API, or the vacation name).
+/// running         Whether the event is currently in effect.
+/// start_date      "YYYY-MM-DD", in thermostat time. Likewise end_date.
+/// start_time      "HH:MM:SS", in thermostat time. Likewise end_time.
+/// heat_hold_temp  The heat setpoint during the event, in Ecobee units. Likewise cool_hold_temp.
+/// fan             The fan mode during the event ("auto" or "on").
+#[derive(Clone, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct StatusResponseThermostatEvent {
+    #[serde(rename="type")]
+    pub event_type: String,
+    pub name: String,
+    pub running: bool,
+    #[serde(rename="startDate")]
+    pub start_date: String,
+    #[serde(rename="startTime")]
+    pub start_time: String,
+    #[serde(rename="endDate")]
+    pub end_date: String,
+    #[serde(rename="endTime")]
+    pub end_time: String,
+    #[serde(rename="heatHoldTemp")]
+    pub heat_hold_temp: f64,
+    #[serde(rename="coolHoldTemp")]
+    pub cool_hold_temp: f64,
+    pub fan: String,
+}
+
+/// # ThermostatVacation
+/// 
+/// A vacation event, along with the thermostat it is set on.
+#[derive(Clone, Debug)]
+pub struct ThermostatVacation {
+    pub thermostat: ThermostatMeta,
+    pub vacation: StatusResponseThermostatEvent,
+}
+
+/// # RemoteSensorCapability
+/// 
+/// One reading from a sensor. The value is a string: tenths of a degree Fahrenheit for "temperature",
+/// a percentage for "humidity", "true" or "false" for "occupancy", and "unknown" when the sensor can't tell.
+#[derive(Clone, Deserialize, Debug)]
+pub struct RemoteSensorCapability {
+    #[serde(rename="type")]
+    pub capability_type: String,
+    pub value: String,
+}
+
+/// # RemoteSensor
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/objects/RemoteSensor.shtml
+/// 
+/// A SmartSensor paired with the thermostat (or the thermostat's own sensor, of type "thermostat").
+/// 
+/// id            The sensor identifier (eg "rs:100", or "ei:0" for the thermostat itself).
+/// name          The sensor name (usually the room).
+/// sensor_type   "ecobee3_remote_sensor", "thermostat", etc.
+/// in_use        Whether the sensor is part of the current climate's temperature average.
+/// capabilities  The readings the sensor reports.
+#[derive(Clone, Deserialize, Debug)]
+pub struct RemoteSensor {
+    pub id: String,
+    pub name: String,
+    #[serde(rename="type")]
+    pub sensor_type: String,
+    #[serde(rename="inUse", default)]
+    pub in_use: bool,
+    #[serde(rename="capability", default)]
+    pub capabilities: Vec<RemoteSensorCapability>,
+}
+
+impl RemoteSensor {
+
+    /// # capability(&self, capability_type: &str) -> Option<&str>
+    /// 
+    /// The value of a reading, if the sensor has it and it is known.
+    pub fn capability(&self, capability_type: &str) -> Option<&str> {
+        self.capabilities.iter()
+            .find(|c| c.capability_type == capability_type)
+            .map(|c| c.value.as_str())
+            .filter(|v| *v != "unknown")
+    }
+
+    /// # temperature(&self) -> Option<f64>
+    /// 
+    /// The temperature, in Ecobee units.
+    pub fn temperature(&self) -> Option<f64> {
+        self.capability("temperature")?.parse().ok()
+    }
+
+    /// # humidity(&self) -> Option<f64>
+    pub fn humidity(&self) -> Option<f64> {
+        self.capability("humidity")?.parse().ok()
+    }
+
+    /// # occupied(&self) -> Option<bool>
+    pub fn occupied(&self) -> Option<bool> {
+        self.capability("occupancy")?.parse().ok()
+    }
+
+    /// # reading(&self, unit: TemperatureUnit) -> SensorReading
+    pub fn reading(&self, unit: TemperatureUnit) -> SensorReading {
+        SensorReading {
+            name: self.name.clone(),
+            temperature: self.temperature().map(|t| unit.from_ecobee(t)),
+            humidity: self.humidity(),
+            occupied: self.occupied(),
+        }
+    }
 }
 
+/// # SensorReading
+/// 
+/// What a sensor reports, with the temperature converted to a display unit. Each reading is None when unknown
+/// (or when the sensor doesn't have it, eg humidity on a SmartSensor).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SensorReading {
+    pub name: String,
+    pub temperature: Option<f64>,
+    pub humidity: Option<f64>,
+    pub occupied: Option<bool>,
+}
+
+/// # Alert
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/objects/Alert.shtml
+/// 
+/// acknowledge_ref    The reference to acknowledge the alert with (see `EcobeeClient::acknowledge_alert`).
+/// date               "YYYY-MM-DD" the alert was raised, in thermostat time.
+/// time               "HH:MM:SS" the alert was raised, in thermostat time.
+/// severity           "high", "medium", or "low".
+/// text               The alert message.
+/// alert_number       The Ecobee alert code.
+/// notification_type  The kind of alert (eg "hvac", "furnaceFilter", "lowTemp", "alert").
+#[derive(Clone, Deserialize, Debug, Default, Serialize)]
+#[serde(default)]
+pub struct Alert {
+    #[serde(rename="acknowledgeRef")]
+    pub acknowledge_ref: String,
+    pub date: String,
+    pub time: String,
+    pub severity: String,
+    pub text: String,
+    #[serde(rename="alertNumber")]
+    pub alert_number: i64,
+    #[serde(rename="notificationType")]
+    pub notification_type: String,
+}
+
+/// # ThermostatAlert
+/// 
+/// An alert, along with the thermostat that raised it.
+#[derive(Clone, Debug)]
+pub struct ThermostatAlert {
+    pub thermostat: ThermostatMeta,
+    pub alert: Alert,
+}
+
+/// # StatusResponseThermostat
+/// 
+/// equipment_status  The equipment currently running, comma separated (empty when idle).
+/// events            The events (holds, vacations, etc) set on the thermostat, running or scheduled.
+/// remote_sensors    Every sensor, including the thermostat's own.
+/// alerts            The alerts waiting to be acknowledged.
 #[derive(Deserialize, Debug)]
 pub struct StatusResponseThermostat {
     pub identifier: String,
     pub name: String,
     pub settings: StatusResponseThermostatSettings,
-    pub runtime: StatusResponseThermostatRuntime
+    pub runtime: StatusResponseThermostatRuntime,
+    #[serde(rename="equipmentStatus", default)]
+    pub equipment_status: String,
+    #[serde(default)]
+    pub events: Vec<StatusResponseThermostatEvent>,
+    #[serde(rename="remoteSensors", default)]
+    pub remote_sensors: Vec<RemoteSensor>,
+    #[serde(default)]
+    pub alerts: Vec<Alert>,
+}
+
+impl StatusResponseThermostat {
+
+    /// # running(&self) -> Vec<&str>
+    /// 
+    /// The equipment currently running (eg "heatPump", "fan").
+    pub fn running(&self) -> Vec<&str> {
+        self.equipment_status.split(',').filter(|e| !e.is_empty()).collect()
+    }
+
+    /// # hold(&self) -> Option<&StatusResponseThermostatEvent>
+    /// 
+    /// The hold currently in effect, if any.
+    pub fn hold(&self) -> Option<&StatusResponseThermostatEvent> {
+        self.events.iter().find(|e| e.running && e.event_type == "hold")
+    }
+
+    /// # vacations(&self) -> Vec<&StatusResponseThermostatEvent>
+    /// 
+    /// The vacation events, running or scheduled.
+    pub fn vacations(&self) -> Vec<&StatusResponseThermostatEvent> {
+        self.events.iter().filter(|e| e.event_type == "vacation").collect()
+    }
+
+    /// # meta(&self) -> ThermostatMeta
+    /// 
+    /// The identifier and name, as stored locally for updates.
+    pub fn meta(&self) -> ThermostatMeta {
+        ThermostatMeta { identifier: self.identifier.clone(), name: self.name.clone() }
+    }
+}
+
+/// # ThermostatStatus
+/// 
+/// The result of a status call: full details for each thermostat, and their combined HVAC mode.
+#[derive(Debug)]
+pub struct ThermostatStatus {
+    pub thermostats: Vec<StatusResponseThermostat>,
+    pub hvac_mode: HvacModeStatus,
+}
+
+impl ThermostatStatus {
+
+    /// # metas(&self) -> Vec<ThermostatMeta>
+    pub fn metas(&self) -> Vec<ThermostatMeta> {
+        self.thermostats.iter().map(|t| t.meta()).collect()
+    }
+}
+
+/// # SummaryResponse
+/// 
+/// The raw /thermostatSummary response. Both lists are colon separated strings, parsed into `ThermostatSummary`.
+/// 
+/// revisions  "identifier:name:connected:thermostatRevision:alertsRevision:runtimeRevision:intervalRevision" per thermostat.
+/// statuses   "identifier:equipment,equipment,..." per thermostat (only with includeEquipmentStatus).
+#[derive(Deserialize, Debug)]
+pub struct SummaryResponse {
+    #[serde(rename="revisionList", default)]
+    pub revisions: Vec<String>,
+    #[serde(rename="statusList", default)]
+    pub statuses: Vec<String>,
+}
+
+/// # ThermostatRevision
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/operations/get-thermostat-summary.shtml
+/// 
+/// identifier           The thermostat identifier.
+/// name                 The thermostat name.
+/// connected            Whether the thermostat is currently connected to the server.
+/// thermostat_revision  Changes whenever the thermostat settings, program, or events change (including the HVAC Mode).
+/// alerts_revision      Changes whenever alerts are raised or acknowledged.
+/// runtime_revision     Changes whenever the thermostat reports new runtime values (every few minutes).
+/// interval_revision    Changes whenever a new runtime interval is recorded (every 15 minutes).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ThermostatRevision {
+    pub identifier: String,
+    pub name: String,
+    pub connected: bool,
+    pub thermostat_revision: String,
+    pub alerts_revision: String,
+    pub runtime_revision: String,
+    pub interval_revision: String,
+}
+
+impl FromStr for ThermostatRevision {
+    type Err = Error;
+
+    /// Parse one entry of the revision list. The name may itself contain colons.
+    fn from_str(s: &str) -> Result<Self> {
+        let fields: Vec<&str> = s.split(':').collect();
+        if fields.len() < 7 {
+            return Err(Error::Json(format!("Unexpected thermostat revision \"{s}\".")));
+        }
+        let revisions = &fields[fields.len() - 5..];
+        Ok(ThermostatRevision {
+            identifier: fields[0].to_string(),
+            name: fields[1..fields.len() - 5].join(":"),
+            connected: revisions[0] == "true",
+            thermostat_revision: revisions[1].to_string(),
+            alerts_revision: revisions[2].to_string(),
+            runtime_revision: revisions[3].to_string(),
+            interval_revision: revisions[4].to_string(),
+        })
+    }
+}
+
+/// # EquipmentStatus
+/// 
+/// identifier  The thermostat identifier.
+/// running     The equipment currently running (eg "heatPump", "compCool1", "fan"), empty when idle.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EquipmentStatus {
+    pub identifier: String,
+    pub running: Vec<String>,
+}
+
+impl FromStr for EquipmentStatus {
+    type Err = Error;
+
+    /// Parse one entry of the status list.
+    fn from_str(s: &str) -> Result<Self> {
+        let (identifier, running) = s.split_once(':')
+            .ok_or_else(|| Error::Json(format!("Unexpected equipment status \"{s}\".")))?;
+        Ok(EquipmentStatus {
+            identifier: identifier.to_string(),
+            running: running.split(',').filter(|e| !e.is_empty()).map(str::to_string).collect(),
+        })
+    }
+}
+
+/// # ThermostatSummary
+/// 
+/// The result of a summary call: revisions and running equipment for each thermostat.
+/// A fraction of the cost of a status call, so suited to polling for changes.
+#[derive(Clone, Debug, Default)]
+pub struct ThermostatSummary {
+    pub revisions: Vec<ThermostatRevision>,
+    pub equipment: Vec<EquipmentStatus>,
+}
+
+impl TryFrom<SummaryResponse> for ThermostatSummary {
+    type Error = Error;
+
+    fn try_from(response: SummaryResponse) -> Result<Self> {
+        Ok(ThermostatSummary {
+            revisions: response.revisions.iter().map(|r| r.parse()).collect::<Result<_>>()?,
+            equipment: response.statuses.iter().map(|s| s.parse()).collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl ThermostatSummary {
+
+    /// # changed_since(&self, previous: &ThermostatSummary) -> Vec<&str>
+    /// 
+    /// Identifiers of the thermostats whose thermostat revision (settings, program, or events) differs from `previous`,
+    /// including any that weren't in it. Runtime revisions are ignored, as they change every few minutes regardless.
+    pub fn changed_since(&self, previous: &ThermostatSummary) -> Vec<&str> {
+        self.differing(previous, |r| &r.thermostat_revision)
+    }
+
+    /// # runtime_changed_since(&self, previous: &ThermostatSummary) -> Vec<&str>
+    /// 
+    /// Identifiers of the thermostats with new runtime values (temperatures, humidity, sensor readings) since `previous`,
+    /// including any that weren't in it.
+    pub fn runtime_changed_since(&self, previous: &ThermostatSummary) -> Vec<&str> {
+        self.differing(previous, |r| &r.runtime_revision)
+    }
+
+    /// # differing(&self, previous: &ThermostatSummary, revision: impl Fn(&ThermostatRevision) -> &String) -> Vec<&str>
+    fn differing(&self, previous: &ThermostatSummary, revision: impl Fn(&ThermostatRevision) -> &String) -> Vec<&str> {
+        self.revisions.iter()
+            .filter(|r| !previous.revisions.iter().any(|p| p.identifier == r.identifier && revision(p) == revision(r)))
+            .map(|r| r.identifier.as_str())
+            .collect()
+    }
 }
 
 ///  # TokenResponse
@@ -75,12 +692,115 @@ pub struct TokenResponse {
     pub scope: String
 }
 
+/// # ResponseStatus
+/// 
+/// The `status` object included in every API response. A code of 0 means success.
+/// 
+/// https://www.ecobee.com/home/developer/api/documentation/v1/objects/Status.shtml
+#[derive(Clone, Deserialize, Debug)]
+pub struct ResponseStatus {
+    pub code: i64,
+    pub message: String
+}
+
+/// # UpdateResponse
+/// 
+/// The response to a POST to the update endpoint. The HTTP status can be 200 while `status.code` reports a failure.
+#[derive(Deserialize, Debug)]
+pub struct UpdateResponse {
+    pub status: ResponseStatus
+}
+
+impl UpdateResponse {
+
+    /// # into_result(self) -> Result<()>
+    /// 
+    /// Ok for a status code of 0, otherwise an Api error with the code and message.
+    pub fn into_result(self) -> Result<()> {
+        match self.status.code {
+            0 => Ok(()),
+            code => Err(Error::Api { code, message: self.status.message })
+        }
+    }
+}
+
+/// # UpdateResult
+/// 
+/// The outcome of an update for a single thermostat.
+#[derive(Debug)]
+pub struct UpdateResult {
+    pub thermostat: ThermostatMeta,
+    pub result: Result<()>
+}
+
+/// # UpdateReport
+/// 
+/// The outcome of a bulk update: what was attempted (eg "cool", "resume program"), and the result per thermostat.
+#[derive(Debug)]
+pub struct UpdateReport {
+    pub description: String,
+    pub results: Vec<UpdateResult>
+}
+
+impl UpdateReport {
+
+    /// # succeeded(&self) -> Vec<&ThermostatMeta>
+    pub fn succeeded(&self) -> Vec<&ThermostatMeta> {
+        self.results.iter().filter(|r| r.result.is_ok()).map(|r| &r.thermostat).collect()
+    }
+
+    /// # failed(&self) -> Vec<&ThermostatMeta>
+    pub fn failed(&self) -> Vec<&ThermostatMeta> {
+        self.results.iter().filter(|r| r.result.is_err()).map(|r| &r.thermostat).collect()
+    }
+
+    /// # into_result(self) -> Result<()>
+    /// 
+    /// Ok if every thermostat was updated, otherwise an Update error naming the ones that failed.
+    pub fn into_result(self) -> Result<()> {
+        let failed: Vec<String> = self.failed().iter().map(|t| t.name.clone()).collect();
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Update { failed, total: self.results.len() })
+        }
+    }
+}
+
+impl fmt::Display for UpdateReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed: Vec<&str> = self.failed().iter().map(|t| t.name.as_str()).collect();
+        write!(f, "{}: {} succeeded, {} failed", self.description, self.succeeded().len(), failed.len())?;
+        if !failed.is_empty() {
+            write!(f, " ({})", failed.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 /// # Tokens
 /// 
-/// Convenience struct to pass both access and refresh tokens around.
+/// Convenience struct to pass both access and refresh tokens around, along with when the access token was issued and when it expires.
+/// The times are unknown for tokens stored by older versions (or the PIN code stored in place of an access token).
+#[derive(Clone, Deserialize, Debug, Serialize)]
 pub struct Tokens {
     pub access_token: String,
-    pub refresh_token: String
+    pub refresh_token: String,
+    pub issued_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>
+}
+
+impl Tokens {
+
+    /// # expires_within(&self, margin: chrono::Duration) -> bool
+    /// 
+    /// Whether the access token has expired, or will within `margin`. Unknown expiry counts as expired.
+    pub fn expires_within(&self, margin: chrono::Duration) -> bool {
+        match self.expires_at {
+            Some(expires_at) => Utc::now() + margin >= expires_at,
+            None => true
+        }
+    }
 }
 
 /// # ThermostatMeta
@@ -0,0 +1,101 @@
+use log::{debug, info};
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::ecobee::client::EcobeeClient;
+use crate::ecobee::models::{HvacMode, ThermostatMeta, ThermostatSummary};
+use crate::error::Result;
+
+/// # VerifyOptions
+/// 
+/// How long to keep polling after an update, and how quickly.
+/// 
+/// timeout        Give up on thermostats that haven't converged after this long.
+/// initial_delay  Wait before the first read-back, doubled after each one.
+/// max_delay      Upper bound on the wait between read-backs.
+#[derive(Clone, Copy, Debug)]
+pub struct VerifyOptions {
+    pub timeout: Duration,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        VerifyOptions {
+            timeout: Duration::from_secs(120),
+            initial_delay: Duration::from_secs(2),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl EcobeeClient {
+
+    /// # hvac_modes(&mut self, thermostats: &[ThermostatMeta]) -> Result<BTreeMap<String, HvacMode>>
+    /// 
+    /// Read back the current HVAC Mode of the given thermostats, keyed by identifier, with a single request (per page of results).
+    /// Only settings are requested, to keep the call light.
+    pub fn hvac_modes(&mut self, thermostats: &[ThermostatMeta]) -> Result<BTreeMap<String, HvacMode>> {
+        let identifiers: Vec<&str> = thermostats.iter().map(|t| t.identifier.as_str()).collect();
+        let thermostats = self.thermostats(serde_json::json!({
+            "selectionType": "thermostats",
+            "selectionMatch": identifiers.join(","),
+            "includeSettings": true
+        }))?;
+        Ok(thermostats.into_iter().map(|t| (t.identifier, t.settings.hvac_mode)).collect())
+    }
+
+    /// # verify_hvac_mode(&mut self, thermostats: &[ThermostatMeta], mode: HvacMode, options: &VerifyOptions) -> Result<Vec<ThermostatMeta>>
+    /// 
+    /// Poll (with exponential backoff) until every thermostat reports `mode`, or the timeout elapses.
+    /// Thermostats drop out of the polling once they match.
+    /// 
+    /// Each poll is a thermostat summary call. The modes are only read back for thermostats whose revision
+    /// has changed since the previous poll (all of them on the first), to keep off the /thermostat endpoint.
+    /// 
+    /// Returns the thermostats that never converged (empty if all did).
+    pub fn verify_hvac_mode(&mut self, thermostats: &[ThermostatMeta], mode: HvacMode, options: &VerifyOptions) -> Result<Vec<ThermostatMeta>> {
+        let deadline = Instant::now() + options.timeout;
+        let mut pending: Vec<ThermostatMeta> = thermostats.to_vec();
+        let mut delay = options.initial_delay;
+        let mut previous: Option<ThermostatSummary> = None;
+        while !pending.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            thread::sleep(delay.min(deadline - now));
+            delay = (delay * 2).min(options.max_delay);
+
+            let summary = self.thermostat_summary()?;
+            let changed: Vec<ThermostatMeta> = match &previous {
+                Some(previous) => {
+                    let changed = summary.changed_since(previous);
+                    pending.iter().filter(|t| changed.contains(&t.identifier.as_str())).cloned().collect()
+                },
+                None => pending.clone()
+            };
+            previous = Some(summary);
+            if changed.is_empty() {
+                debug!("No changes to the remaining thermostats yet, waiting for {mode}");
+                continue;
+            }
+
+            let modes = self.hvac_modes(&changed)?;
+            pending.retain(|t| match modes.get(&t.identifier) {
+                Some(current) if *current == mode => {
+                    info!("{} is now {mode}", t.name);
+                    false
+                },
+                Some(current) => {
+                    debug!("{} is still {current}, waiting for {mode}", t.name);
+                    true
+                },
+                None => true
+            });
+        }
+        Ok(pending)
+    }
+}
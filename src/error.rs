@@ -0,0 +1,126 @@
+use std::fmt;
+
+/// # Error
+///
+/// Crate-wide error type.
+///
+/// Config     Reading or writing local configuration (api key, tokens, thermostats, weather settings).
+/// Token      Missing or unusable access/refresh tokens.
+/// Status     The API responded with a non-success HTTP status (code and response body).
+/// Transport  The request never got a response (DNS, connection, TLS, etc).
+/// Json       The response body could not be decoded.
+/// Api        The API responded successfully, but reported a failure in its `status` object (code and message).
+/// Update     Some thermostats failed to update (their names, and how many were attempted).
+/// Verify     Some thermostats never reported the requested setting after an update (their names).
+#[derive(Debug)]
+pub enum Error {
+    Config(String),
+    Token(String),
+    Status { code: u16, body: String },
+    Transport(String),
+    Json(String),
+    Api { code: i64, message: String },
+    Update { failed: Vec<String>, total: usize },
+    Verify { unconverged: Vec<String> },
+}
+
+/// # Result<T>
+///
+/// Convenience alias for results using the crate-wide error type.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+
+    /// # exit_code() -> i32
+    ///
+    /// Process exit code for this error, so scripts can tell failures apart.
+    /// (2 is left to clap argument errors, and a panic exits with 101; 1 is unused).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Config(_) => 3,
+            Error::Token(_) => 4,
+            Error::Status { .. } => 5,
+            Error::Transport(_) => 6,
+            Error::Json(_) => 7,
+            Error::Api { .. } => 8,
+            Error::Update { .. } => 9,
+            Error::Verify { .. } => 10,
+        }
+    }
+
+    /// # ecobee_status(&self) -> Option<i64>
+    /// 
+    /// The Ecobee `status.code` reported with this error, from an Api error or an error response body.
+    /// 
+    /// https://www.ecobee.com/home/developer/api/documentation/v1/general/api-status-codes.shtml
+    pub fn ecobee_status(&self) -> Option<i64> {
+        match self {
+            Error::Api { code, .. } => Some(*code),
+            Error::Status { body, .. } => serde_json::from_str::<serde_json::Value>(body).ok()
+                .and_then(|v| v["status"]["code"].as_i64()),
+            _ => None
+        }
+    }
+
+    /// # oauth_error(&self) -> Option<String>
+    /// 
+    /// The OAuth `error` code in an error response body (eg "authorization_pending" from the token endpoint).
+    pub fn oauth_error(&self) -> Option<String> {
+        match self {
+            Error::Status { body, .. } => serde_json::from_str::<serde_json::Value>(body).ok()
+                .and_then(|v| v["error"].as_str().map(str::to_string)),
+            _ => None
+        }
+    }
+
+    /// # is_token_expired(&self) -> bool
+    /// 
+    /// Whether Ecobee rejected the request because the access token has expired (status 14).
+    pub fn is_token_expired(&self) -> bool {
+        self.ecobee_status() == Some(ECOBEE_TOKEN_EXPIRED)
+    }
+
+    /// # is_retryable(&self) -> bool
+    /// 
+    /// Whether trying the same request again might succeed: transport errors, 429 and 5xx responses.
+    /// An expired token comes back as a 500, but needs a refresh rather than a retry.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Transport(_) => true,
+            Error::Status { code, .. } => (*code == 429 || *code >= 500) && !self.is_token_expired(),
+            _ => false
+        }
+    }
+}
+
+/// Ecobee status code for an expired access token.
+pub const ECOBEE_TOKEN_EXPIRED: i64 = 14;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(msg) => write!(f, "Configuration error: {msg}"),
+            Error::Token(msg) => write!(f, "Token error: {msg}"),
+            Error::Status { code, body } => write!(f, "HTTP status {code}: {body}"),
+            Error::Transport(msg) => write!(f, "Transport error: {msg}"),
+            Error::Json(msg) => write!(f, "Error decoding response: {msg}"),
+            Error::Api { code, message } => write!(f, "Ecobee status {code}: {message}"),
+            Error::Update { failed, total } => write!(f, "{} of {total} thermostats failed to update: {}", failed.len(), failed.join(", ")),
+            Error::Verify { unconverged } => write!(f, "Thermostats did not take the change: {}", unconverged.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ureq::Error> for Error {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(code, response) => Error::Status {
+                code,
+                body: response.into_string().unwrap_or_default(),
+            },
+            ureq::Error::Transport(t) => Error::Transport(t.to_string()),
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use log::{debug, warn};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::thread;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+/// # RetryPolicy
+/// 
+/// How failed requests are retried.
+/// 
+/// retries     Additional attempts after the first one (0 disables retrying).
+/// base_delay  Delay before the first retry, doubled for each one after (with jitter).
+/// max_delay   Upper bound on any single delay, backoff or Retry-After (default 2 minutes, so a typical
+///             Retry-After of a minute is waited for). A Retry-After longer than this is not waited for.
+/// 
+/// Only transport errors, 429 (Too Many Requests) and 5xx responses are retried (see `Error::is_retryable`).
+/// Other 4xx responses (bad credentials, bad requests) and expired tokens fail immediately.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 3,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(120),
+        }
+    }
+}
+
+impl RetryPolicy {
+
+    /// # none() -> RetryPolicy
+    /// 
+    /// Single attempt, no retries.
+    pub fn none() -> Self {
+        RetryPolicy { retries: 0, ..Default::default() }
+    }
+
+    /// # backoff(&self, attempt: u32) -> Duration
+    /// 
+    /// Delay before retry number `attempt` (starting at 1): exponential, capped, with the upper half jittered.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(2u32.saturating_pow(attempt - 1)).min(self.max_delay);
+        let half = exponential / 2;
+        half + half.mul_f64(jitter())
+    }
+}
+
+/// # call(policy: &RetryPolicy, request: ureq::Request) -> Result<ureq::Response>
+/// 
+/// Send a request without a body, retrying according to the policy.
+pub fn call(policy: &RetryPolicy, request: ureq::Request) -> Result<ureq::Response> {
+    with_retries(policy, request.url(), || request.clone().call().map_err(Box::new))
+}
+
+/// # send_json(policy: &RetryPolicy, request: ureq::Request, body: serde_json::Value) -> Result<ureq::Response>
+/// 
+/// Send a request with a JSON body, retrying according to the policy.
+/// Callers opt in to retrying: pass `RetryPolicy::none()` unless sending the body twice is harmless.
+pub fn send_json(policy: &RetryPolicy, request: ureq::Request, body: serde_json::Value) -> Result<ureq::Response> {
+    with_retries(policy, request.url(), || request.clone().send_json(body.clone()).map_err(Box::new))
+}
+
+/// # with_retries(policy, url, attempt) -> Result<ureq::Response>
+/// 
+/// Run `attempt` until it succeeds, fails with a non-retryable error, or the retries run out.
+fn with_retries(
+    policy: &RetryPolicy, url: &str,
+    mut attempt: impl FnMut() -> std::result::Result<ureq::Response, Box<ureq::Error>>
+) -> Result<ureq::Response> {
+    let mut retry = 0;
+    loop {
+        let error = match attempt() {
+            Ok(response) => return Ok(response),
+            Err(e) => e
+        };
+        let wait = retry_after(&error);
+        let error = Error::from(*error);
+        if retry >= policy.retries || !error.is_retryable() {
+            return Err(error);
+        }
+        retry += 1;
+        let delay = match wait {
+            Some(wait) if wait > policy.max_delay => {
+                debug!("Retry-After of {wait:?} exceeds the maximum delay, not retrying.");
+                return Err(error);
+            },
+            Some(wait) => wait,
+            None => policy.backoff(retry)
+        };
+        warn!("Request to {url} failed ({}), retry {retry} of {} in {delay:?}", describe(&error), policy.retries);
+        thread::sleep(delay);
+    }
+}
+
+/// # retry_after(error: &ureq::Error) -> Option<Duration>
+/// 
+/// The Retry-After header of an error response, if it is given in seconds.
+fn retry_after(error: &ureq::Error) -> Option<Duration> {
+    match error {
+        ureq::Error::Status(_, response) => response.header("Retry-After")
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs),
+        ureq::Error::Transport(_) => None
+    }
+}
+
+/// # describe(error: &Error) -> String
+/// 
+/// Short description for the retry warning (without the response body).
+fn describe(error: &Error) -> String {
+    match error {
+        Error::Status { code, .. } => format!("status {code}"),
+        e => e.to_string()
+    }
+}
+
+/// # jitter() -> f64
+/// 
+/// A random fraction in [0, 1), from the randomly keyed std hasher (good enough to spread out retries).
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
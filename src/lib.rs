@@ -0,0 +1,22 @@
+//! # Bulk Ecobee Thermostat Control
+//! 
+//! Library behind the `bulk-ecobee-thermostat-control` command line tool.
+//! 
+//! `EcobeeClient` wraps the Ecobee API calls (authorization, tokens, status and updates),
+//! with token persistence supplied through the `TokenStore` trait.
+//! `storage::FileTokenStore` keeps them in the same files the command line tool uses, for the profile given by a `storage::Location`.
+
+pub mod config;
+pub mod ecobee;
+pub mod error;
+pub mod http;
+pub mod output;
+pub mod prompt;
+pub mod secrets;
+pub mod selection;
+pub mod storage;
+pub mod units;
+pub mod weather;
+
+pub use ecobee::client::{EcobeeClient, TokenStore};
+pub use error::{Error, Result};
@@ -1,13 +1,19 @@
-use chrono::Local;
-use clap::Parser;
-use log::info;
+use chrono::{Local, NaiveDateTime};
+use clap::{ArgGroup, Parser};
+use log::{error, info, warn};
 use simplelog::*;
+use std::process;
+use std::time::Duration;
 use time::UtcOffset;
 
-
-mod ecobee;
-mod storage;
-mod weather;
+use bulk_ecobee_thermostat_control::ecobee::{self, models::{FanMode, Hold, HoldType, HvacMode, ThermostatMeta, UpdateReport, Vacation}};
+use bulk_ecobee_thermostat_control::ecobee::verify::VerifyOptions;
+use bulk_ecobee_thermostat_control::config::Config;
+use bulk_ecobee_thermostat_control::output::{AlertsDocument, OutputFormat, StatusDocument, UpdateDocument, VacationsDocument};
+use bulk_ecobee_thermostat_control::selection::{ResolvedSelection, Selection};
+use bulk_ecobee_thermostat_control::storage::Location;
+use bulk_ecobee_thermostat_control::units::TemperatureUnit;
+use bulk_ecobee_thermostat_control::{error, storage, weather, EcobeeClient};
 
 /// # Args struct for Clap
 /// 
@@ -15,24 +21,50 @@ mod weather;
 /// 
 /// Stick to the long form for clarity (and to avoid collision).
 /// For convenience, allow the short form for `refresh` and `status`.
+/// 
+/// The setup arguments form the "setup_steps" group: only one may be given, and only along with the logging and profile arguments.
+/// The hvac mode arguments form the "mode" group: at most one may be given.
+/// Likewise for the hold length arguments ("hold_length"), which default to holding until the next program change.
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("setup_steps").multiple(false).conflicts_with_all([
+    "weather", "check_weather", "refresh", "status", "alerts", "ack_alert", "mode", "resume", "hold_heat", "vacation", "delete_vacation", "vacations", "only", "except", "group", "all_profiles"
+])))]
+#[command(group(ArgGroup::new("mode").multiple(false)))]
+#[command(group(ArgGroup::new("hold_length").multiple(false)))]
 struct Args {
 
     // Setup & configuration arguments
 
-    #[arg(long, exclusive=true)]
-    key: bool,
+    /// Store the Ecobee api key: the value given, or else prompt for it
+    #[arg(long, group="setup_steps", value_name="KEY", num_args=0..=1)]
+    key: Option<Option<String>>,
+
+    /// Store the Ecobee api key read from stdin
+    #[arg(long, group="setup_steps")]
+    key_stdin: bool,
     
-    #[arg(long, exclusive=true)]
+    #[arg(long, group="setup_steps")]
     pin: bool,
 
-    #[arg(long, exclusive=true)]
+    #[arg(long, group="setup_steps")]
     auth: bool,
 
-    #[arg(long, exclusive=true)]
+    /// Request a PIN, wait for it to be registered in the Ecobee portal, then store the tokens and thermostats (replaces --pin and --auth)
+    #[arg(long, group="setup_steps")]
+    setup: bool,
+
+    #[arg(long, group="setup_steps")]
     weather_setup: bool,
 
+    /// Change weather settings without prompting (eg interval=30 cool_above=75, empty to unset)
+    #[arg(long, group="setup_steps", value_name="NAME=VALUE", num_args=1..)]
+    weather_set: Vec<String>,
+
+    /// Replace the weather settings with those in a YAML file (same format as weather.yaml)
+    #[arg(long, group="setup_steps", value_name="FILE")]
+    weather_import: Option<String>,
+
     // Normal runtime arguments
 
     #[arg(short, long)]
@@ -41,7 +73,29 @@ struct Args {
     #[arg(short, long)]
     debug: bool,
 
-    #[arg(long, conflicts_with_all=["heat", "cool", "off", "refresh", "status"])]
+    /// Print status and update results as text, json, ndjson, yaml, or table
+    #[arg(long, value_name="FORMAT", default_value="text")]
+    output: OutputFormat,
+
+    /// Refuse to run if the config directory or secret files are accessible by other users
+    #[arg(long)]
+    strict_permissions: bool,
+
+    /// Keep all settings and tokens in this directory (default: $ECOBEE_CONFIG_DIR, or the XDG config and state directories)
+    #[arg(long, value_name="PATH")]
+    config_dir: Option<String>,
+
+    // Profiles (separate Ecobee accounts)
+
+    /// Use this profile's api key, tokens, thermostats and weather settings (default: $ECOBEE_PROFILE, or the default profile)
+    #[arg(long, value_name="NAME")]
+    profile: Option<String>,
+
+    /// Run the command once for every profile that has been set up
+    #[arg(long, conflicts_with_all=["profile", "weather"])]
+    all_profiles: bool,
+
+    #[arg(long, conflicts_with_all=["mode", "refresh", "status"])]
     weather: bool,
 
     #[arg(long)]
@@ -53,14 +107,185 @@ struct Args {
     #[arg(short, long)]
     status: bool,
 
-    #[arg(long, conflicts_with_all=["heat", "off", "weather"])]
+    /// List the alerts (eg filter changes, HVAC errors, low temperature) waiting on every thermostat
+    #[arg(long, conflicts_with="weather")]
+    alerts: bool,
+
+    /// Acknowledge (dismiss) an alert by the reference shown with --alerts (repeatable)
+    #[arg(long, value_name="REF", conflicts_with="weather")]
+    ack_alert: Vec<String>,
+
+    #[arg(long, group="mode")]
     cool: bool,
 
-    #[arg(long, conflicts_with_all=["cool", "off", "weather"])]
+    #[arg(long, group="mode")]
     heat: bool,
 
-    #[arg(long, conflicts_with_all=["cool", "heat", "weather"])]
+    #[arg(long, group="mode")]
     off: bool,
+
+    #[arg(long, group="mode")]
+    auto: bool,
+
+    #[arg(long, group="mode")]
+    aux_heat: bool,
+
+    #[arg(long, conflicts_with_all=["weather", "hold_heat"])]
+    resume: bool,
+
+    // Thermostat selection, for the commands above and weather mode
+
+    /// Only these thermostats (name glob or identifier, repeatable)
+    #[arg(long, value_name="NAME|ID")]
+    only: Vec<String>,
+
+    /// Skip these thermostats (name glob or identifier, repeatable)
+    #[arg(long, value_name="NAME|ID")]
+    except: Vec<String>,
+
+    /// Only the thermostats in this group from groups.yaml (repeatable)
+    #[arg(long, value_name="GROUP")]
+    group: Vec<String>,
+
+    // Holds (in your configured unit)
+
+    #[arg(long, requires="hold_cool", conflicts_with="weather")]
+    hold_heat: Option<f64>,
+
+    #[arg(long, requires="hold_heat")]
+    hold_cool: Option<f64>,
+
+    #[arg(long, requires="hold_heat", group="hold_length")]
+    hold_hours: Option<u32>,
+
+    #[arg(long, requires="hold_heat", group="hold_length", value_parser=parse_datetime)]
+    hold_until: Option<NaiveDateTime>,
+
+    #[arg(long, requires="hold_heat", group="hold_length")]
+    hold_indefinite: bool,
+
+    // Vacations (in your configured unit)
+
+    /// Create a vacation with this name on the selected thermostats
+    #[arg(long, value_name="NAME", requires_all=["vacation_start", "vacation_end", "vacation_heat", "vacation_cool"], conflicts_with="weather")]
+    vacation: Option<String>,
+
+    /// When the vacation starts, "YYYY-MM-DD HH:MM" in thermostat time
+    #[arg(long, requires="vacation", value_parser=parse_datetime)]
+    vacation_start: Option<NaiveDateTime>,
+
+    /// When the vacation ends, "YYYY-MM-DD HH:MM" in thermostat time
+    #[arg(long, requires="vacation", value_parser=parse_datetime)]
+    vacation_end: Option<NaiveDateTime>,
+
+    #[arg(long, requires="vacation")]
+    vacation_heat: Option<f64>,
+
+    #[arg(long, requires="vacation")]
+    vacation_cool: Option<f64>,
+
+    /// Fan mode during the vacation: auto or on
+    #[arg(long, value_name="MODE", requires="vacation", default_value="auto")]
+    vacation_fan: FanMode,
+
+    /// Delete the vacation with this name from the selected thermostats
+    #[arg(long, value_name="NAME", conflicts_with="weather")]
+    delete_vacation: Option<String>,
+
+    /// List the vacations, running or scheduled, on every thermostat
+    #[arg(long, conflicts_with="weather")]
+    vacations: bool,
+
+    // Verification of mode changes
+
+    /// After a mode change, poll until every thermostat reports the new mode
+    #[arg(long, requires="mode")]
+    verify: bool,
+
+    /// Seconds to keep polling before giving up on a thermostat
+    #[arg(long, requires="verify", default_value_t=120)]
+    verify_timeout: u64,
+
+    /// Times to resend the mode change to thermostats that never reported it
+    #[arg(long, requires="verify", default_value_t=0)]
+    verify_retries: u32,
+}
+
+impl Args {
+
+    /// # documents(&self) -> usize
+    /// 
+    /// How many documents a run in one profile prints with a document format: one each for status, alerts,
+    /// acknowledgements, vacations, and the updates (resume, mode, hold and vacation changes together).
+    fn documents(&self) -> usize {
+        let update = self.resume || self.hvac_mode().is_some() || self.hold_heat.is_some()
+            || self.vacation.is_some() || self.delete_vacation.is_some();
+        [self.status, self.alerts, !self.ack_alert.is_empty(), self.vacations, update].into_iter().filter(|d| *d).count()
+    }
+
+    /// # hvac_mode(&self) -> Option<HvacMode>
+    /// 
+    /// The hvac mode requested on the command line, if any.
+    fn hvac_mode(&self) -> Option<HvacMode> {
+        if self.cool { Some(HvacMode::Cool) }
+        else if self.heat { Some(HvacMode::Heat) }
+        else if self.off { Some(HvacMode::Off) }
+        else if self.auto { Some(HvacMode::Auto) }
+        else if self.aux_heat { Some(HvacMode::AuxHeatOnly) }
+        else { None }
+    }
+
+    /// # selection(&self, location: &Location) -> error::Result<ResolvedSelection>
+    /// 
+    /// The thermostat selection, with groups expanded from the profile's groups.yaml (only loaded if groups were given).
+    fn selection(&self, location: &Location) -> error::Result<ResolvedSelection> {
+        let selection = Selection { only: self.only.clone(), except: self.except.clone(), groups: self.group.clone() };
+        let groups = if selection.groups.is_empty() { Default::default() } else { storage::load_groups(location)? };
+        selection.resolve(&groups)
+    }
+
+    /// # hold(&self, unit: TemperatureUnit) -> Option<Hold>
+    /// 
+    /// The hold requested on the command line, if any, converted from `unit` to Ecobee units.
+    fn hold(&self, unit: TemperatureUnit) -> Option<Hold> {
+        let (heat, cool) = (self.hold_heat?, self.hold_cool?);
+        let hold_type = if let Some(hours) = self.hold_hours { HoldType::HoldHours(hours) }
+        else if let Some(end) = self.hold_until { HoldType::DateTime(end) }
+        else if self.hold_indefinite { HoldType::Indefinite }
+        else { HoldType::NextTransition };
+        Some(Hold { heat_hold_temp: unit.to_ecobee(heat), cool_hold_temp: unit.to_ecobee(cool), hold_type })
+    }
+
+    /// # vacation(&self, unit: TemperatureUnit) -> error::Result<Option<Vacation>>
+    /// 
+    /// The vacation requested on the command line, if any, converted from `unit` to Ecobee units.
+    /// A vacation that doesn't end after it starts is a Config error.
+    fn vacation(&self, unit: TemperatureUnit) -> error::Result<Option<Vacation>> {
+        let (Some(name), Some(start), Some(end), Some(heat), Some(cool)) =
+            (&self.vacation, self.vacation_start, self.vacation_end, self.vacation_heat, self.vacation_cool) else {
+            return Ok(None);
+        };
+        if end <= start {
+            return Err(error::Error::Config(format!("Vacation \"{name}\" must end after it starts ({start} to {end}).")));
+        }
+        Ok(Some(Vacation {
+            name: name.clone(),
+            start,
+            end,
+            heat_hold_temp: unit.to_ecobee(heat),
+            cool_hold_temp: unit.to_ecobee(cool),
+            fan: self.vacation_fan,
+        }))
+    }
+}
+
+/// # parse_datetime(value: &str) -> Result<NaiveDateTime, String>
+/// 
+/// Parse "YYYY-MM-DD HH:MM" (or with seconds) for --hold-until, --vacation-start and --vacation-end.
+fn parse_datetime(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M"))
+        .map_err(|e| format!("expected \"YYYY-MM-DD HH:MM\": {e}"))
 }
 
 fn main() {
@@ -80,75 +305,426 @@ fn main() {
         }
     };
 
-    SimpleLogger::init(log_level, log_config).unwrap();
+    // SimpleLogger writes everything below Error to stdout, which would get mixed into a json or yaml document.
+    if args.output.is_document() {
+        WriteLogger::init(log_level, log_config, std::io::stderr()).unwrap();
+    } else {
+        SimpleLogger::init(log_level, log_config).unwrap();
+    }
 
     info!("Bulk Ecobee Thermostat Control Run @ {}", Local::now().to_rfc2822());
 
+    if let Err(e) = run(args) {
+        error!("{e}");
+        process::exit(e.exit_code());
+    }
+}
+
+/// # client(config: &Config, location: &Location, output: OutputFormat) -> error::Result<EcobeeClient>
+/// 
+/// Ecobee client using the profile's stored app key and tokens. Progress is kept off stdout when printing a json or yaml document.
+fn client(config: &Config, location: &Location, output: OutputFormat) -> error::Result<EcobeeClient> {
+    Ok(EcobeeClient::new(storage::load_app_key(location)?, Box::new(storage::FileTokenStore::new(location.clone())))
+        .with_base_url(&config.ecobee_base_url())
+        .with_retry_policy(config.retry_policy())
+        .quiet(output.is_document()))
+}
+
+/// # status(client: &mut EcobeeClient, location: &Location, output: OutputFormat, unit: TemperatureUnit) -> error::Result<()>
+/// 
+/// Print the status of every registered thermostat in the requested format and unit,
+/// and refresh the local store of thermostat identifiers and names for use with the update commands.
+fn status(client: &mut EcobeeClient, location: &Location, output: OutputFormat, unit: TemperatureUnit) -> error::Result<()> {
+    let status = client.thermostat_status()?;
+    let document = StatusDocument::new(location.profile().map(str::to_string), &status.thermostats, unit);
+    match output {
+        OutputFormat::Text => print_status(&document),
+        OutputFormat::Table => {
+            print!("{}", document.table());
+            if document.thermostats.iter().any(|t| !t.sensors.is_empty()) {
+                print!("\n{}", document.sensor_table());
+            }
+        },
+        _ => println!("{}", output.render(&document)?)
+    }
+    storage::write_thermostats(location, status.metas())
+}
+
+/// # print_status(status: &StatusDocument)
+/// 
+/// Display the name, identifier, HVAC Mode, equipment running, Actual Temperature, Actual Humidity,
+/// setpoints, any hold, any alerts, and the readings of every sensor for each thermostat.
+fn print_status(status: &StatusDocument) {
+    println!("\nThermostats\n");
+    for thermostat in &status.thermostats {
+        println!("Thermostat {} (id: {})", thermostat.name, thermostat.identifier);
+        println!("HVAC Mode: {}", thermostat.hvac_mode);
+        if !thermostat.running.is_empty() {
+            println!("Running: {}", thermostat.running.join(", "));
+        }
+        println!("Actual Temperature: {}, Actual Humidity: {}%", thermostat.degrees(thermostat.actual_temperature), thermostat.actual_humidity);
+        println!("Desired Cool: {}, Desired Heat: {}", thermostat.degrees(thermostat.desired_cool), thermostat.degrees(thermostat.desired_heat));
+        if let Some(hold) = &thermostat.hold {
+            println!("Hold: heat {}, cool {} until {}", thermostat.degrees(hold.heat), thermostat.degrees(hold.cool), hold.until);
+        }
+        for alert in &thermostat.alerts {
+            println!("Alert ({}): {} [ref: {}]", alert.severity, alert.text, alert.acknowledge_ref);
+        }
+        for sensor in &thermostat.sensors {
+            let mut readings = vec![];
+            if let Some(temperature) = sensor.temperature {
+                readings.push(thermostat.degrees(temperature));
+            }
+            if let Some(humidity) = sensor.humidity {
+                readings.push(format!("{humidity}%"));
+            }
+            match sensor.occupied {
+                Some(true) => readings.push("occupied".to_string()),
+                Some(false) => readings.push("unoccupied".to_string()),
+                None => ()
+            }
+            println!("Sensor {}: {}", sensor.name, if readings.is_empty() { "no readings".to_string() } else { readings.join(", ") });
+        }
+        println!();
+    }
+}
+
+/// # update(client: &mut EcobeeClient, location: &Location, args: &Args, hold: Option<Hold>, vacation: Option<Vacation>, unit: TemperatureUnit) -> error::Result<()>
+/// 
+/// Resume the program and/or apply the mode, hold, and vacation changes to the selected stored thermostats.
+/// A vacation is deleted before one is created, so the same name can be given to both to replace it.
+/// A summary is printed after each step. Every step is attempted, and any thermostat failing makes the whole run fail.
+fn update(client: &mut EcobeeClient, location: &Location, args: &Args, hold: Option<Hold>, vacation: Option<Vacation>, unit: TemperatureUnit) -> error::Result<()> {
+    let thermostats = args.selection(location)?.apply(storage::load_thermostats(location)?)?;
+    let mut reports = vec![];
+    let mut unconverged = vec![];
+    if args.resume {
+        reports.push(client.resume_program(&thermostats, true)?);
+    }
+    if let Some(mode) = args.hvac_mode() {
+        let report = client.update_thermostats(&thermostats, mode)?;
+        if args.verify {
+            let updated: Vec<ThermostatMeta> = report.succeeded().into_iter().cloned().collect();
+            unconverged = verify(client, &updated, mode, args)?;
+        }
+        reports.push(report);
+    }
+    if let Some(hold) = hold {
+        reports.push(client.set_hold(&thermostats, &hold, unit)?);
+    }
+    if let Some(name) = &args.delete_vacation {
+        reports.push(client.delete_vacation(&thermostats, name)?);
+    }
+    if let Some(vacation) = vacation {
+        reports.push(client.create_vacation(&thermostats, &vacation)?);
+    }
+    print_reports(location, args.output, &reports, &unconverged)?;
+    reports.into_iter().try_for_each(|r| r.into_result())?;
+    if unconverged.is_empty() {
+        Ok(())
+    } else {
+        Err(error::Error::Verify { unconverged: unconverged.into_iter().map(|t| t.name).collect() })
+    }
+}
+
+/// # print_reports(location: &Location, output: OutputFormat, reports: &[UpdateReport], unconverged: &[ThermostatMeta]) -> error::Result<()>
+/// 
+/// Print the outcome of each update step in the requested format.
+fn print_reports(location: &Location, output: OutputFormat, reports: &[UpdateReport], unconverged: &[ThermostatMeta]) -> error::Result<()> {
+    match output {
+        OutputFormat::Text => {
+            println!();
+            for report in reports {
+                println!("{report}");
+            }
+        },
+        _ => {
+            let names = unconverged.iter().map(|t| t.name.clone()).collect();
+            let document = UpdateDocument::new(location.profile().map(str::to_string), reports, names);
+            match output {
+                OutputFormat::Table => print!("{}", document.table()),
+                _ => println!("{}", output.render(&document)?)
+            }
+        }
+    }
+    Ok(())
+}
+
+/// # alerts(client: &mut EcobeeClient, location: &Location, output: OutputFormat) -> error::Result<()>
+/// 
+/// Print every alert waiting on the registered thermostats, with the reference to acknowledge it by.
+fn alerts(client: &mut EcobeeClient, location: &Location, output: OutputFormat) -> error::Result<()> {
+    let document = AlertsDocument::new(location.profile().map(str::to_string), client.alerts()?);
+    match output {
+        OutputFormat::Text => {
+            if document.alerts.is_empty() {
+                println!("No alerts.");
+            }
+            for a in &document.alerts {
+                println!("{} @ {} {} ({}): {} [ref: {}]", a.name, a.alert.date, a.alert.time, a.alert.severity, a.alert.text, a.alert.acknowledge_ref);
+            }
+        },
+        OutputFormat::Table => print!("{}", document.table()),
+        _ => println!("{}", output.render(&document)?)
+    }
+    Ok(())
+}
+
+/// # vacations(client: &mut EcobeeClient, location: &Location, output: OutputFormat, unit: TemperatureUnit) -> error::Result<()>
+/// 
+/// Print every vacation, running or scheduled, on the registered thermostats.
+fn vacations(client: &mut EcobeeClient, location: &Location, output: OutputFormat, unit: TemperatureUnit) -> error::Result<()> {
+    let document = VacationsDocument::new(location.profile().map(str::to_string), client.vacations()?, unit);
+    match output {
+        OutputFormat::Text => {
+            if document.vacations.is_empty() {
+                println!("No vacations.");
+            }
+            for v in &document.vacations {
+                let running = if v.running { " (running)" } else { "" };
+                println!("{}: {} from {} to {}, heat {} / cool {}, fan {}{running}", v.name, v.vacation, v.start, v.end, v.degrees(v.heat), v.degrees(v.cool), v.fan);
+            }
+        },
+        OutputFormat::Table => print!("{}", document.table()),
+        _ => println!("{}", output.render(&document)?)
+    }
+    Ok(())
+}
+
+/// # ack_alerts(client: &mut EcobeeClient, location: &Location, refs: &[String], output: OutputFormat) -> error::Result<()>
+/// 
+/// Acknowledge each alert by reference, on the thermostat that raised it.
+/// A reference that doesn't match any current alert is an error (and nothing is acknowledged).
+fn ack_alerts(client: &mut EcobeeClient, location: &Location, refs: &[String], output: OutputFormat) -> error::Result<()> {
+    let alerts = client.alerts()?;
+    let mut acknowledge = vec![];
+    for ack_ref in refs {
+        match alerts.iter().find(|a| &a.alert.acknowledge_ref == ack_ref) {
+            Some(alert) => acknowledge.push(alert),
+            None => return Err(error::Error::Config(format!("No alert with reference \"{ack_ref}\" (see --alerts).")))
+        }
+    }
+    let mut reports = vec![];
+    for alert in acknowledge {
+        reports.push(client.acknowledge_alert(&alert.thermostat, &alert.alert.acknowledge_ref)?);
+    }
+    print_reports(location, output, &reports, &[])?;
+    reports.into_iter().try_for_each(|r| r.into_result())
+}
+
+/// # verify(client: &mut EcobeeClient, thermostats: &[ThermostatMeta], mode: HvacMode, args: &Args) -> error::Result<Vec<ThermostatMeta>>
+/// 
+/// Poll until the thermostats report `mode`, resending the change to stragglers up to `--verify-retries` times.
+/// Returns the thermostats that never converged.
+fn verify(client: &mut EcobeeClient, thermostats: &[ThermostatMeta], mode: HvacMode, args: &Args) -> error::Result<Vec<ThermostatMeta>> {
+    let options = VerifyOptions { timeout: Duration::from_secs(args.verify_timeout), ..Default::default() };
+    progress(args, format!("Verifying {} thermostats report {mode} (up to {} seconds)", thermostats.len(), args.verify_timeout));
+    let mut unconverged = client.verify_hvac_mode(thermostats, mode, &options)?;
+    for attempt in 1..=args.verify_retries {
+        if unconverged.is_empty() {
+            break;
+        }
+        let names: Vec<&str> = unconverged.iter().map(|t| t.name.as_str()).collect();
+        progress(args, format!("Retry {attempt} of {} for {}", args.verify_retries, names.join(", ")));
+        let report = client.update_thermostats(&unconverged, mode)?;
+        let updated: Vec<ThermostatMeta> = report.succeeded().into_iter().cloned().collect();
+        let mut still_pending: Vec<ThermostatMeta> = report.failed().into_iter().cloned().collect();
+        still_pending.extend(client.verify_hvac_mode(&updated, mode, &options)?);
+        unconverged = still_pending;
+    }
+    if unconverged.is_empty() {
+        progress(args, format!("Verified: every thermostat reports {mode}"));
+    } else {
+        let names: Vec<&str> = unconverged.iter().map(|t| t.name.as_str()).collect();
+        progress(args, format!("Never converged to {mode}: {}", names.join(", ")));
+    }
+    Ok(unconverged)
+}
+
+/// # progress(args: &Args, message: String)
+/// 
+/// Print a progress message, or log it (at info level) when --output is json or yaml, to leave stdout to the document.
+fn progress(args: &Args, message: String) {
+    if args.output.is_document() {
+        info!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+/// # instruction(args: &Args, message: String)
+/// 
+/// Print a message the user has to see (eg the authorization PIN): to stdout, or to stderr when --output is json or yaml,
+/// to leave stdout to the document.
+fn instruction(args: &Args, message: String) {
+    if args.output.is_document() {
+        eprintln!("{message}");
+    } else {
+        println!("{message}");
+    }
+}
+
+/// # run(args: Args) -> error::Result<()>
+/// 
+/// Select the configuration directory (moving over the one used by older versions, the first time) and the profile, and run the command in it.
+/// With --all-profiles the command runs in every profile in turn; a failure in one doesn't stop the others,
+/// but the first error is returned once they have all run.
+/// 
+/// JSON output is printed as JSON Lines (ndjson), one document per line, whenever more than one document will be printed
+/// (with --all-profiles, or several commands together), so that the output as a whole is still valid.
+fn run(mut args: Args) -> error::Result<()> {
+    if args.output == OutputFormat::Json && (args.all_profiles || args.documents() > 1) {
+        args.output = OutputFormat::Ndjson;
+    }
+    let base = Location::new(args.config_dir.clone());
+    storage::migrate_legacy_dir(&base)?;
+    if !args.all_profiles {
+        let profile = args.profile.clone()
+            .or_else(|| std::env::var(storage::PROFILE_ENV).ok().filter(|p| !p.is_empty()));
+        return run_profile(&args, &base.with_profile(profile)?);
+    }
+    let profiles = storage::list_profiles(&base)?;
+    if profiles.is_empty() {
+        return Err(error::Error::Config("No profiles have been set up. Run --key first.".to_string()));
+    }
+    let mut first_error = None;
+    for profile in profiles {
+        if !args.output.is_document() {
+            println!("== Profile: {} ==", profile.as_deref().unwrap_or("default"));
+        }
+        if let Err(e) = base.with_profile(profile.clone()).and_then(|location| run_profile(&args, &location)) {
+            error!("Profile {}: {e}", profile.as_deref().unwrap_or("default"));
+            first_error.get_or_insert(e);
+        }
+    }
+    first_error.map_or(Ok(()), Err)
+}
+
+/// # run_profile(args: &Args, location: &Location) -> error::Result<()>
+/// 
+/// Dispatch the command line arguments in the profile at `location`, stopping at the first error.
+fn run_profile(args: &Args, location: &Location) -> error::Result<()> {
+
+    // Check the secrets aren't exposed to other users.
+
+    let problems = storage::check_permissions(location)?;
+    if !problems.is_empty() && args.strict_permissions {
+        return Err(error::Error::Config(format!("Insecure permissions: {}", problems.join("; "))));
+    }
+    for problem in problems {
+        warn!("{problem}");
+    }
+
     // Handle setup first,
 
     // Setup Step 1
-    if args.key {
-        ecobee::api::api_key();
-        return
+    if let Some(key) = &args.key {
+        return match key {
+            Some(key) => ecobee::api::set_api_key(location, key),
+            None => ecobee::api::api_key(location)
+        };
+    }
+    if args.key_stdin {
+        return ecobee::api::api_key_from(location, std::io::stdin());
     }
 
+    // Weather Setup
+
+    if args.weather_setup {
+        return weather::settings::setup(location);
+    }
+    if !args.weather_set.is_empty() {
+        return weather::settings::set(location, &args.weather_set);
+    }
+    if let Some(path) = &args.weather_import {
+        return weather::settings::import(location, path);
+    }
+
+    let config = storage::load_config(location)?;
+    let unit = config.temperature_unit(storage::load_weather_settings(location)?.metric);
+    let vacation = args.vacation(unit)?; // Checked before anything is sent.
+    let mut client = client(&config, location, args.output)?;
+
     // Setup Step 2
     if args.pin {
-        ecobee::api::authorize();
-        return
+        instruction(args, "Authorizing!".to_string());
+        let auth = client.authorize()?;
+        instruction(args, format!("Ecobee Authorization PIN: {}", auth.ecobee_pin));
+        instruction(args, format!("(expires in {} minutes)", auth.expires_in));
+        instruction(args, "Log into the Ecobee web portal and register the application using the PIN in your `My Apps` widget.".to_string());
+        return Ok(())
     }
 
     // Setup Step 3
     if args.auth {
-        ecobee::api::get_tokens_with_code();
-        ecobee::api::thermostat_status(); // Get status, and refresh thermostat data locally.
-        return
+        client.get_tokens_with_code()?;
+        return status(&mut client, location, args.output, unit); // Get status, and refresh thermostat data locally.
     }
 
-    // Weather Setup
-
-    if args.weather_setup {
-        weather::settings::setup();
-        return
+    // Setup Steps 2 and 3 in one go
+    if args.setup {
+        let auth = client.authorize()?;
+        instruction(args, format!("Ecobee Authorization PIN: {}", auth.ecobee_pin));
+        instruction(args, "Log into the Ecobee web portal and register the application using the PIN in your `My Apps` widget.".to_string());
+        instruction(args, format!("Waiting up to {} minutes for the app to be registered...", auth.expires_in));
+        client.wait_for_authorization(&auth)?;
+        instruction(args, "Authorized!".to_string());
+        return status(&mut client, location, args.output, unit);
     }
 
     // Weather Mode
 
     if args.weather {
-        weather::api::run();
-        return
+        return weather::api::run(&mut client, location, &config, &args.selection(location)?);
     }
 
     // Run refresh and status if they were specified.
 
     if args.refresh {
-        ecobee::api::refresh_tokens();
+        client.refresh_tokens()?;
     }
 
     if args.status {
-        ecobee::api::thermostat_status();
+        status(&mut client, location, args.output, unit)?;
     }
 
     if args.check_weather {
-        weather::api::check();
+        weather::api::check(location, &config, args.output.is_document())?;
     }
 
-    // If an argument to change the hvac mode is present, apply it and exit.
+    if args.alerts {
+        alerts(&mut client, location, args.output)?;
+    }
 
-    if args.cool {
-        ecobee::api::refresh_tokens();
-        ecobee::api::update_thermostats("cool");
-        return
+    if !args.ack_alert.is_empty() {
+        ack_alerts(&mut client, location, &args.ack_alert, args.output)?;
     }
 
-    if args.heat {
-        ecobee::api::refresh_tokens();
-        ecobee::api::update_thermostats("heat");
-        return
+    if args.vacations {
+        vacations(&mut client, location, args.output, unit)?;
+    }
+
+    // If an argument to resume the program, change the hvac mode, hold the temperature, or create or delete a vacation
+    // is present, apply it.
+
+    let hold = args.hold(unit);
+    if args.resume || args.hvac_mode().is_some() || hold.is_some() || args.delete_vacation.is_some() || vacation.is_some() {
+        update(&mut client, location, args, hold, vacation, unit)?;
     }
 
-    if args.off {
-        ecobee::api::refresh_tokens();
-        ecobee::api::update_thermostats("off");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::Args;
+
+    /// Clap only checks the argument definitions (eg a group sharing an argument's id) when parsing in a debug build,
+    /// so check them here rather than on the first run.
+    #[test]
+    fn args_are_consistent() {
+        Args::command().debug_assert();
     }
 }
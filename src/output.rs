@@ -0,0 +1,474 @@
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::ecobee::models::{Alert, HvacMode, SensorReading, StatusResponseThermostat, ThermostatAlert, ThermostatVacation, UpdateReport};
+use crate::error::{Error, Result};
+use crate::units::TemperatureUnit;
+
+/// # OutputFormat
+/// 
+/// How status and update results are printed (--output).
+/// 
+/// text    Free-form text, for people (the default).
+/// json    A JSON document, for scripts and dashboards.
+/// yaml    A YAML document, likewise.
+/// ndjson  JSON Lines: each document on one line, so several can follow each other (eg with --all-profiles).
+/// table   Aligned columns, one row per thermostat.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Ndjson,
+    Yaml,
+    Table,
+}
+
+impl OutputFormat {
+
+    /// # is_document(&self) -> bool
+    /// 
+    /// Whether this is a machine-readable format. Nothing but the document should then be printed to stdout.
+    pub fn is_document(&self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Ndjson | OutputFormat::Yaml)
+    }
+
+    /// # render<T: Serialize>(&self, document: &T) -> Result<String>
+    /// 
+    /// The document as JSON (pretty printed, or on one line for ndjson) or YAML. Text and tables are rendered by the caller, so asking for them here is an error.
+    pub fn render<T: Serialize>(&self, document: &T) -> Result<String> {
+        match self {
+            OutputFormat::Json => serde_json::to_string_pretty(document).map_err(|e| Error::Json(e.to_string())),
+            OutputFormat::Ndjson => serde_json::to_string(document).map_err(|e| Error::Json(e.to_string())),
+            OutputFormat::Yaml => serde_yaml::to_string(document).map(|yaml| format!("---\n{yaml}")).map_err(|e| Error::Json(e.to_string())),
+            OutputFormat::Text | OutputFormat::Table => Err(Error::Config(format!("{self} output is not a document format.")))
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "yaml" => Ok(OutputFormat::Yaml),
+            "table" => Ok(OutputFormat::Table),
+            _ => Err(format!("unknown output format \"{s}\" (expected text, json, ndjson, yaml, or table)"))
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self {
+            OutputFormat::Text => "text",
+            OutputFormat::Json => "json",
+            OutputFormat::Ndjson => "ndjson",
+            OutputFormat::Yaml => "yaml",
+            OutputFormat::Table => "table",
+        };
+        write!(f, "{format}")
+    }
+}
+
+/// # Table
+/// 
+/// Rows of text printed in columns padded to the widest cell, under a header and a rule.
+#[derive(Debug)]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+
+    /// # new(headers: &[&str]) -> Table
+    pub fn new(headers: &[&str]) -> Self {
+        Table { headers: headers.iter().map(|h| h.to_string()).collect(), rows: vec![] }
+    }
+
+    /// # push(&mut self, row: Vec<String>)
+    /// 
+    /// Add a row, with one cell per header.
+    pub fn push(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths: Vec<usize> = (0..self.headers.len())
+            .map(|i| self.rows.iter()
+                .filter_map(|row| row.get(i))
+                .chain(std::iter::once(&self.headers[i]))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or(0))
+            .collect();
+        let line = |f: &mut fmt::Formatter<'_>, cells: &[String]| {
+            let padded: Vec<String> = widths.iter().enumerate()
+                .map(|(i, width)| format!("{:<width$}", cells.get(i).map(String::as_str).unwrap_or("")))
+                .collect();
+            writeln!(f, "{}", padded.join("  ").trim_end())
+        };
+        line(f, &self.headers)?;
+        line(f, &widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<_>>())?;
+        for row in &self.rows {
+            line(f, row)?;
+        }
+        Ok(())
+    }
+}
+
+/// # HoldDocument
+/// 
+/// The hold in effect on a thermostat, with setpoints in the document's units.
+/// 
+/// heat   The heat setpoint held.
+/// cool   The cool setpoint held.
+/// until  When the hold ends ("YYYY-MM-DD HH:MM:SS", thermostat time).
+#[derive(Debug, Serialize)]
+pub struct HoldDocument {
+    pub heat: f64,
+    pub cool: f64,
+    pub until: String,
+}
+
+/// # ThermostatDocument
+/// 
+/// The status of one thermostat, with temperatures converted to `units`.
+/// 
+/// identifier          The thermostat identifier.
+/// name                The thermostat name.
+/// hvac_mode           The current HVAC Mode.
+/// running             The equipment currently running (eg "heatPump", "fan"), empty when idle.
+/// actual_temperature  The indoor temperature.
+/// actual_humidity     The indoor humidity (percent).
+/// desired_heat        The heat setpoint currently in effect.
+/// desired_cool        The cool setpoint currently in effect.
+/// hold                The hold in effect, if any.
+/// sensors             The readings from every sensor (including the thermostat's own).
+/// alerts              The alerts waiting to be acknowledged.
+/// units               The unit of the temperatures.
+#[derive(Debug, Serialize)]
+#[serde(rename_all="camelCase")]
+pub struct ThermostatDocument {
+    pub identifier: String,
+    pub name: String,
+    pub hvac_mode: HvacMode,
+    pub running: Vec<String>,
+    pub actual_temperature: f64,
+    pub actual_humidity: f64,
+    pub desired_heat: f64,
+    pub desired_cool: f64,
+    pub hold: Option<HoldDocument>,
+    pub sensors: Vec<SensorReading>,
+    pub alerts: Vec<Alert>,
+    pub units: TemperatureUnit,
+}
+
+impl ThermostatDocument {
+
+    /// # new(thermostat: &StatusResponseThermostat, unit: TemperatureUnit) -> ThermostatDocument
+    pub fn new(thermostat: &StatusResponseThermostat, unit: TemperatureUnit) -> Self {
+        ThermostatDocument {
+            identifier: thermostat.identifier.clone(),
+            name: thermostat.name.clone(),
+            hvac_mode: thermostat.settings.hvac_mode,
+            running: thermostat.running().into_iter().map(str::to_string).collect(),
+            actual_temperature: unit.from_ecobee(thermostat.runtime.actual_temperature),
+            actual_humidity: thermostat.runtime.actual_humidity,
+            desired_heat: unit.from_ecobee(thermostat.runtime.desired_heat),
+            desired_cool: unit.from_ecobee(thermostat.runtime.desired_cool),
+            hold: thermostat.hold().map(|hold| HoldDocument {
+                heat: unit.from_ecobee(hold.heat_hold_temp),
+                cool: unit.from_ecobee(hold.cool_hold_temp),
+                until: format!("{} {}", hold.end_date, hold.end_time),
+            }),
+            sensors: thermostat.remote_sensors.iter().map(|sensor| sensor.reading(unit)).collect(),
+            alerts: thermostat.alerts.clone(),
+            units: unit,
+        }
+    }
+
+    /// # degrees(&self, degrees: f64) -> String
+    /// 
+    /// A temperature from this document with its unit symbol (eg "71.2°F").
+    pub fn degrees(&self, degrees: f64) -> String {
+        format!("{degrees}{}", self.units.symbol())
+    }
+}
+
+/// # StatusDocument
+/// 
+/// The output of --status: the profile (omitted for the default profile) and every thermostat.
+#[derive(Debug, Serialize)]
+pub struct StatusDocument {
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub profile: Option<String>,
+    pub thermostats: Vec<ThermostatDocument>,
+}
+
+impl StatusDocument {
+
+    /// # new(profile: Option<String>, thermostats: &[StatusResponseThermostat], unit: TemperatureUnit) -> StatusDocument
+    pub fn new(profile: Option<String>, thermostats: &[StatusResponseThermostat], unit: TemperatureUnit) -> Self {
+        StatusDocument { profile, thermostats: thermostats.iter().map(|t| ThermostatDocument::new(t, unit)).collect() }
+    }
+
+    /// # table(&self) -> Table
+    /// 
+    /// One row per thermostat. Idle equipment and no hold are shown as "-".
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(&["Name", "Mode", "Running", "Temp", "Humidity", "Heat", "Cool", "Alerts", "Hold"]);
+        for t in &self.thermostats {
+            let running = if t.running.is_empty() { "-".to_string() } else { t.running.join(", ") };
+            let hold = match &t.hold {
+                Some(hold) => format!("{}/{} until {}", t.degrees(hold.heat), t.degrees(hold.cool), hold.until),
+                None => "-".to_string()
+            };
+            table.push(vec![
+                t.name.clone(),
+                t.hvac_mode.to_string(),
+                running,
+                t.degrees(t.actual_temperature),
+                format!("{}%", t.actual_humidity),
+                t.degrees(t.desired_heat),
+                t.degrees(t.desired_cool),
+                t.alerts.len().to_string(),
+                hold,
+            ]);
+        }
+        table
+    }
+
+    /// # sensor_table(&self) -> Table
+    /// 
+    /// One row per sensor, under the name of its thermostat. Unknown readings are shown as "-".
+    pub fn sensor_table(&self) -> Table {
+        let mut table = Table::new(&["Thermostat", "Sensor", "Temp", "Humidity", "Occupied"]);
+        for t in &self.thermostats {
+            for sensor in &t.sensors {
+                table.push(vec![
+                    t.name.clone(),
+                    sensor.name.clone(),
+                    sensor.temperature.map_or("-".to_string(), |temperature| t.degrees(temperature)),
+                    sensor.humidity.map_or("-".to_string(), |humidity| format!("{humidity}%")),
+                    sensor.occupied.map_or("-".to_string(), |occupied| if occupied { "yes" } else { "no" }.to_string()),
+                ]);
+            }
+        }
+        table
+    }
+}
+
+/// # AlertDocument
+/// 
+/// An alert, with the identifier and name of the thermostat that raised it.
+#[derive(Debug, Serialize)]
+pub struct AlertDocument {
+    pub identifier: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub alert: Alert,
+}
+
+/// # AlertsDocument
+/// 
+/// The output of --alerts: the profile (omitted for the default profile) and every alert waiting to be acknowledged.
+#[derive(Debug, Serialize)]
+pub struct AlertsDocument {
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub profile: Option<String>,
+    pub alerts: Vec<AlertDocument>,
+}
+
+impl AlertsDocument {
+
+    /// # new(profile: Option<String>, alerts: Vec<ThermostatAlert>) -> AlertsDocument
+    pub fn new(profile: Option<String>, alerts: Vec<ThermostatAlert>) -> Self {
+        AlertsDocument {
+            profile,
+            alerts: alerts.into_iter().map(|a| AlertDocument {
+                identifier: a.thermostat.identifier,
+                name: a.thermostat.name,
+                alert: a.alert,
+            }).collect(),
+        }
+    }
+
+    /// # table(&self) -> Table
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(&["Thermostat", "Raised", "Severity", "Alert", "Ref"]);
+        for a in &self.alerts {
+            table.push(vec![
+                a.name.clone(),
+                format!("{} {}", a.alert.date, a.alert.time),
+                a.alert.severity.clone(),
+                a.alert.text.clone(),
+                a.alert.acknowledge_ref.clone(),
+            ]);
+        }
+        table
+    }
+}
+
+/// # VacationDocument
+/// 
+/// A vacation event on one thermostat, with temperatures converted to `units`.
+/// 
+/// identifier  The thermostat identifier.
+/// name        The thermostat name.
+/// vacation    The vacation name.
+/// start       When the vacation starts ("YYYY-MM-DD HH:MM:SS", thermostat time). Likewise end.
+/// heat        The heat setpoint during the vacation. Likewise cool.
+/// fan         The fan mode during the vacation.
+/// running     Whether the vacation is in effect now.
+/// units       The unit of the temperatures.
+#[derive(Debug, Serialize)]
+pub struct VacationDocument {
+    pub identifier: String,
+    pub name: String,
+    pub vacation: String,
+    pub start: String,
+    pub end: String,
+    pub heat: f64,
+    pub cool: f64,
+    pub fan: String,
+    pub running: bool,
+    pub units: TemperatureUnit,
+}
+
+impl VacationDocument {
+
+    /// # degrees(&self, degrees: f64) -> String
+    /// 
+    /// A temperature from this document with its unit symbol (eg "55°F").
+    pub fn degrees(&self, degrees: f64) -> String {
+        format!("{degrees}{}", self.units.symbol())
+    }
+}
+
+/// # VacationsDocument
+/// 
+/// The output of --vacations: the profile (omitted for the default profile) and every vacation, running or scheduled.
+#[derive(Debug, Serialize)]
+pub struct VacationsDocument {
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub profile: Option<String>,
+    pub vacations: Vec<VacationDocument>,
+}
+
+impl VacationsDocument {
+
+    /// # new(profile: Option<String>, vacations: Vec<ThermostatVacation>, unit: TemperatureUnit) -> VacationsDocument
+    pub fn new(profile: Option<String>, vacations: Vec<ThermostatVacation>, unit: TemperatureUnit) -> Self {
+        VacationsDocument {
+            profile,
+            vacations: vacations.into_iter().map(|v| VacationDocument {
+                identifier: v.thermostat.identifier,
+                name: v.thermostat.name,
+                start: format!("{} {}", v.vacation.start_date, v.vacation.start_time),
+                end: format!("{} {}", v.vacation.end_date, v.vacation.end_time),
+                heat: unit.from_ecobee(v.vacation.heat_hold_temp),
+                cool: unit.from_ecobee(v.vacation.cool_hold_temp),
+                fan: v.vacation.fan,
+                running: v.vacation.running,
+                vacation: v.vacation.name,
+                units: unit,
+            }).collect(),
+        }
+    }
+
+    /// # table(&self) -> Table
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(&["Thermostat", "Vacation", "Start", "End", "Heat", "Cool", "Fan", "Running"]);
+        for v in &self.vacations {
+            table.push(vec![
+                v.name.clone(),
+                v.vacation.clone(),
+                v.start.clone(),
+                v.end.clone(),
+                v.degrees(v.heat),
+                v.degrees(v.cool),
+                v.fan.clone(),
+                if v.running { "yes" } else { "no" }.to_string(),
+            ]);
+        }
+        table
+    }
+}
+
+/// # ResultDocument
+/// 
+/// The outcome of an update for one thermostat: whether it succeeded, and if not the error.
+#[derive(Debug, Serialize)]
+pub struct ResultDocument {
+    pub identifier: String,
+    pub name: String,
+    pub succeeded: bool,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// # ActionDocument
+/// 
+/// One step of an update (eg "cool", "resume program") and the result for each thermostat.
+#[derive(Debug, Serialize)]
+pub struct ActionDocument {
+    pub action: String,
+    pub results: Vec<ResultDocument>,
+}
+
+impl From<&UpdateReport> for ActionDocument {
+    fn from(report: &UpdateReport) -> Self {
+        ActionDocument {
+            action: report.description.clone(),
+            results: report.results.iter().map(|r| ResultDocument {
+                identifier: r.thermostat.identifier.clone(),
+                name: r.thermostat.name.clone(),
+                succeeded: r.result.is_ok(),
+                error: r.result.as_ref().err().map(|e| e.to_string()),
+            }).collect(),
+        }
+    }
+}
+
+/// # UpdateDocument
+/// 
+/// The output of a mode change, hold, or resume: the profile (omitted for the default profile), each step,
+/// and the names of any thermostats that never reported a new mode (with --verify).
+#[derive(Debug, Serialize)]
+pub struct UpdateDocument {
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub profile: Option<String>,
+    pub actions: Vec<ActionDocument>,
+    pub unconverged: Vec<String>,
+}
+
+impl UpdateDocument {
+
+    /// # new(profile: Option<String>, reports: &[UpdateReport], unconverged: Vec<String>) -> UpdateDocument
+    pub fn new(profile: Option<String>, reports: &[UpdateReport], unconverged: Vec<String>) -> Self {
+        UpdateDocument { profile, actions: reports.iter().map(ActionDocument::from).collect(), unconverged }
+    }
+
+    /// # table(&self) -> Table
+    pub fn table(&self) -> Table {
+        let mut table = Table::new(&["Action", "Name", "Identifier", "Result"]);
+        for action in &self.actions {
+            for r in &action.results {
+                let result = match &r.error {
+                    Some(error) => format!("failed: {error}"),
+                    None => "ok".to_string()
+                };
+                table.push(vec![action.action.clone(), r.name.clone(), r.identifier.clone(), result]);
+            }
+        }
+        table
+    }
+}
@@ -0,0 +1,26 @@
+use std::io::{self, BufRead, Write};
+
+use crate::error::{Error, Result};
+
+/// # read_line(prompt: &str) -> Result<String>
+/// 
+/// Print `prompt` (without a newline) and read one line from stdin, without the line ending.
+/// Running out of input (eg stdin is closed or redirected from an empty file) is an error rather than an empty answer.
+pub fn read_line(prompt: &str) -> Result<String> {
+    print!("{prompt}");
+    io::stdout().flush().map_err(|e| Error::Config(format!("Error writing prompt: {e}")))?;
+    read_line_from(&mut io::stdin().lock())
+}
+
+/// # read_line_from(reader: &mut impl BufRead) -> Result<String>
+/// 
+/// Read one line from `reader`, without the line ending. See `read_line`.
+pub fn read_line_from(reader: &mut impl BufRead) -> Result<String> {
+    let mut line = String::new();
+    let read = reader.read_line(&mut line).map_err(|e| Error::Config(format!("Error reading input: {e}")))?;
+    if read == 0 {
+        return Err(Error::Config("Unexpected end of input.".to_string()));
+    }
+    let trimmed = line.strip_suffix('\n').map(|l| l.strip_suffix('\r').unwrap_or(l)).unwrap_or(&line);
+    Ok(trimmed.to_string())
+}
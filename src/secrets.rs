@@ -0,0 +1,235 @@
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chacha20poly1305::aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::OnceLock;
+
+use crate::error::{Error, Result};
+use crate::storage::{self, Location};
+
+pub static PASSPHRASE_ENV: &str = "ECOBEE_SECRETS_PASSPHRASE";
+pub static PASSPHRASE_FD_ENV: &str = "ECOBEE_SECRETS_PASSPHRASE_FD";
+pub static ENCRYPTED_SUFFIX: &str = ".enc";
+
+/// Current version of the encrypted file format.
+const ENCRYPTED_FILE_VERSION: u32 = 1;
+const SALT_LENGTH: usize = 16;
+
+/// # SecretStore
+/// 
+/// Where the secrets (the Ecobee app key and the tokens) are kept, by file name in a profile's directory (see `storage::Location`).
+/// 
+/// `read` errors if the secret doesn't exist. `write` replaces it.
+pub trait SecretStore {
+    fn read(&self, location: &Location, name: &str, description: &str) -> Result<String>;
+    fn write(&self, location: &Location, name: &str, description: &str, contents: &str) -> Result<()>;
+}
+
+/// # SecretBackend
+/// 
+/// Which `SecretStore` to use, set by `secrets` in config.yaml.
+/// 
+/// file            Plain files in the configuration directory (the default).
+/// encrypted_file  Files encrypted with a passphrase (see `EncryptedFileSecretStore`).
+#[derive(Clone, Copy, Default, Deserialize, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretBackend {
+    #[default]
+    File,
+    EncryptedFile,
+}
+
+impl SecretBackend {
+
+    /// # store(&self) -> Result<Box<dyn SecretStore>>
+    /// 
+    /// The store for this backend. The encrypted backend needs the passphrase (see `passphrase`).
+    pub fn store(&self) -> Result<Box<dyn SecretStore>> {
+        match self {
+            SecretBackend::File => Ok(Box::new(FileSecretStore)),
+            SecretBackend::EncryptedFile => Ok(Box::new(EncryptedFileSecretStore::new(passphrase()?))),
+        }
+    }
+}
+
+/// # FileSecretStore
+/// 
+/// `SecretStore` keeping each secret in a plain file (readable by the owner only).
+pub struct FileSecretStore;
+
+impl SecretStore for FileSecretStore {
+    fn read(&self, location: &Location, name: &str, description: &str) -> Result<String> {
+        storage::read_config_file(location, name, description)
+    }
+
+    fn write(&self, location: &Location, name: &str, description: &str, contents: &str) -> Result<()> {
+        storage::write_config_file(location, name, description, contents)
+    }
+}
+
+/// # EncryptedFile
+/// 
+/// The contents of an encrypted secret file (binary values are base64).
+/// 
+/// version     Format version.
+/// salt        Argon2id salt used to derive the key from the passphrase.
+/// nonce       XChaCha20-Poly1305 nonce.
+/// ciphertext  The encrypted secret, with its authentication tag.
+#[derive(Deserialize, Serialize)]
+struct EncryptedFile {
+    version: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// # EncryptedFileSecretStore
+/// 
+/// `SecretStore` keeping each secret in "<name>.enc", encrypted with XChaCha20-Poly1305
+/// under a key derived from the passphrase with Argon2id (fresh salt and nonce on every write).
+/// 
+/// A plain file left over from the file backend is encrypted, and removed, the first time it is read.
+pub struct EncryptedFileSecretStore {
+    passphrase: String,
+}
+
+impl EncryptedFileSecretStore {
+
+    /// # new(passphrase: String) -> EncryptedFileSecretStore
+    pub fn new(passphrase: String) -> Self {
+        EncryptedFileSecretStore { passphrase }
+    }
+
+    /// # key(&self, salt: &[u8]) -> Result<[u8; 32]>
+    fn key(&self, salt: &[u8]) -> Result<[u8; 32]> {
+        let mut key = [0u8; 32];
+        Argon2::default().hash_password_into(self.passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| Error::Config(format!("Error deriving key from passphrase: {e}")))?;
+        Ok(key)
+    }
+
+    /// # encrypt(&self, contents: &str) -> Result<String>
+    /// 
+    /// The YAML document for `contents`, encrypted.
+    pub fn encrypt(&self, contents: &str) -> Result<String> {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = XChaCha20Poly1305::new(&self.key(&salt)?.into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, contents.as_bytes())
+            .map_err(|e| Error::Config(format!("Error encrypting secret: {e}")))?;
+        serde_yaml::to_string(&EncryptedFile {
+            version: ENCRYPTED_FILE_VERSION,
+            salt: BASE64.encode(salt),
+            nonce: BASE64.encode(nonce),
+            ciphertext: BASE64.encode(ciphertext),
+        }).map_err(|e| Error::Config(format!("Error serializing encrypted secret: {e}")))
+    }
+
+    /// # decrypt(&self, document: &str, description: &str) -> Result<String>
+    /// 
+    /// The secret in an encrypted YAML document. A wrong passphrase or a tampered file fails authentication.
+    pub fn decrypt(&self, document: &str, description: &str) -> Result<String> {
+        let file: EncryptedFile = serde_yaml::from_str(document)
+            .map_err(|e| Error::Config(format!("Error parsing encrypted {description} file: {e}")))?;
+        if file.version > ENCRYPTED_FILE_VERSION {
+            return Err(Error::Config(format!("Encrypted {description} file version {} was written by a newer version of this tool.", file.version)));
+        }
+        let decode = |value: &str| BASE64.decode(value)
+            .map_err(|e| Error::Config(format!("Error decoding encrypted {description} file: {e}")));
+        let (salt, nonce, ciphertext) = (decode(&file.salt)?, decode(&file.nonce)?, decode(&file.ciphertext)?);
+        if nonce.len() != 24 {
+            return Err(Error::Config(format!("Bad nonce in encrypted {description} file.")));
+        }
+        let cipher = XChaCha20Poly1305::new(&self.key(&salt)?.into());
+        let plaintext = cipher.decrypt(XNonce::from_slice(&nonce), ciphertext.as_slice())
+            .map_err(|_| Error::Config(format!("Could not decrypt {description}: wrong passphrase, or the file is corrupt.")))?;
+        String::from_utf8(plaintext).map_err(|e| Error::Config(format!("Decrypted {description} is not text: {e}")))
+    }
+}
+
+impl SecretStore for EncryptedFileSecretStore {
+    /// A plain file left from the "file" backend is read, then replaced with an encrypted one.
+    /// The replacement happens under the tokens lock, after checking again, so that it can't overwrite tokens another process
+    /// has just refreshed (and encrypted). If the lock is held the plain file is left for a later run.
+    fn read(&self, location: &Location, name: &str, description: &str) -> Result<String> {
+        let encrypted_name = format!("{name}{ENCRYPTED_SUFFIX}");
+        let unencrypted = || -> Result<bool> {
+            Ok(!storage::config_file_exists(location, &encrypted_name)? && storage::config_file_exists(location, name)?)
+        };
+        if unencrypted()? {
+            let Some(_lock) = storage::try_lock_tokens(location)? else {
+                debug!("Tokens are locked, leaving the {description} file unencrypted for now.");
+                return storage::read_config_file(location, name, description);
+            };
+            if unencrypted()? {
+                info!("Encrypting {description} file.");
+                let contents = storage::read_config_file(location, name, description)?;
+                self.write(location, name, description, &contents)?;
+                return Ok(contents);
+            }
+        }
+        self.decrypt(&storage::read_config_file(location, &encrypted_name, description)?, description)
+    }
+
+    /// Any plain file left from the "file" backend is removed, since it now holds stale (and unencrypted) secrets.
+    fn write(&self, location: &Location, name: &str, description: &str, contents: &str) -> Result<()> {
+        storage::write_config_file(location, &format!("{name}{ENCRYPTED_SUFFIX}"), description, &self.encrypt(contents)?)?;
+        if storage::config_file_exists(location, name)? {
+            storage::remove_config_file(location, name, description)?;
+        }
+        Ok(())
+    }
+}
+
+static FD_PASSPHRASE: OnceLock<std::result::Result<String, String>> = OnceLock::new();
+
+/// # passphrase() -> Result<String>
+/// 
+/// The passphrase for encrypted secrets, from the ECOBEE_SECRETS_PASSPHRASE environment variable,
+/// or else read from the file descriptor in ECOBEE_SECRETS_PASSPHRASE_FD (eg `ECOBEE_SECRETS_PASSPHRASE_FD=3 thermoctl ... 3< passfile`).
+/// 
+/// The file descriptor can only be read once, so what it held is kept for the rest of the process.
+/// A single trailing newline is dropped.
+pub fn passphrase() -> Result<String> {
+    if let Some(passphrase) = env::var(PASSPHRASE_ENV).ok().filter(|p| !p.is_empty()) {
+        return Ok(passphrase);
+    }
+    let fd = match env::var(PASSPHRASE_FD_ENV) {
+        Ok(fd) => fd,
+        Err(_) => return Err(Error::Config(format!("Encrypted secrets need a passphrase: set {PASSPHRASE_ENV} or {PASSPHRASE_FD_ENV}.")))
+    };
+    FD_PASSPHRASE.get_or_init(|| read_fd(&fd)).clone().map_err(Error::Config)
+}
+
+/// # read_fd(fd: &str) -> std::result::Result<String, String>
+/// 
+/// Reads through a duplicate of the descriptor, so the caller's descriptor is never closed. Standard output and error are refused,
+/// since reading them would fail anyway.
+#[cfg(unix)]
+fn read_fd(fd: &str) -> std::result::Result<String, String> {
+    use std::io::Read;
+    use std::os::unix::io::{BorrowedFd, RawFd};
+    let fd: RawFd = fd.trim().parse().map_err(|e| format!("Bad {PASSPHRASE_FD_ENV} ({fd}): {e}"))?;
+    if fd < 0 || fd == 1 || fd == 2 {
+        return Err(format!("Bad {PASSPHRASE_FD_ENV} ({fd}): use a descriptor opened for the passphrase, eg 3."));
+    }
+    // Safety: the descriptor is handed to us for this purpose, and is only borrowed for as long as it takes to duplicate it.
+    let mut file = std::fs::File::from(unsafe { BorrowedFd::borrow_raw(fd) }.try_clone_to_owned()
+        .map_err(|e| format!("Error reading passphrase from file descriptor {fd}: {e}"))?);
+    let mut passphrase = String::new();
+    file.read_to_string(&mut passphrase).map_err(|e| format!("Error reading passphrase from file descriptor {fd}: {e}"))?;
+    let passphrase = passphrase.strip_suffix('\n').map(|p| p.strip_suffix('\r').unwrap_or(p)).unwrap_or(&passphrase);
+    if passphrase.is_empty() {
+        return Err(format!("Empty passphrase read from file descriptor {fd}."));
+    }
+    Ok(passphrase.to_string())
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: &str) -> std::result::Result<String, String> {
+    Err(format!("{PASSPHRASE_FD_ENV} is only supported on unix, use {PASSPHRASE_ENV}."))
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use crate::ecobee::models::ThermostatMeta;
+use crate::error::{Error, Result};
+
+/// # Groups
+/// 
+/// User-defined named groups of thermostats, stored in "groups.yaml" next to "thermostats.yaml".
+/// Each group is a list of patterns, matched like `--only` (name glob or identifier):
+/// 
+/// upstairs:
+///   - "Upstairs*"
+///   - "511234567890"
+pub type Groups = BTreeMap<String, Vec<String>>;
+
+/// # Selection
+/// 
+/// Which thermostats a command applies to.
+/// 
+/// only    Patterns to include (all thermostats if empty, unless groups are given).
+/// except  Patterns to exclude, applied after `only` and `groups`.
+/// groups  Names of groups to include.
+/// 
+/// A pattern matches a thermostat if it equals the identifier, or glob-matches the name
+/// (`*` any run of characters, `?` any single character, case-insensitive).
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    pub only: Vec<String>,
+    pub except: Vec<String>,
+    pub groups: Vec<String>,
+}
+
+impl Selection {
+
+    /// # resolve(&self, groups: &Groups) -> Result<ResolvedSelection>
+    /// 
+    /// Expand group names into their patterns. Unknown groups are an error.
+    pub fn resolve(&self, groups: &Groups) -> Result<ResolvedSelection> {
+        let mut include = self.only.clone();
+        for name in &self.groups {
+            match groups.get(name) {
+                Some(patterns) => include.extend(patterns.iter().cloned()),
+                None => return Err(Error::Config(format!("Unknown thermostat group: {name}")))
+            }
+        }
+        Ok(ResolvedSelection { include, exclude: self.except.clone() })
+    }
+}
+
+/// # ResolvedSelection
+/// 
+/// A `Selection` with its groups expanded, ready to match thermostats.
+#[derive(Clone, Debug, Default)]
+pub struct ResolvedSelection {
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl ResolvedSelection {
+
+    /// # includes(&self, thermostat: &ThermostatMeta) -> bool
+    pub fn includes(&self, thermostat: &ThermostatMeta) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| matches(p, thermostat)))
+            && !self.exclude.iter().any(|p| matches(p, thermostat))
+    }
+
+    /// # apply(&self, thermostats: Vec<ThermostatMeta>) -> Result<Vec<ThermostatMeta>>
+    /// 
+    /// The selected thermostats. Selecting none at all is an error, rather than silently doing nothing.
+    pub fn apply(&self, thermostats: Vec<ThermostatMeta>) -> Result<Vec<ThermostatMeta>> {
+        let selected: Vec<ThermostatMeta> = thermostats.into_iter().filter(|t| self.includes(t)).collect();
+        if selected.is_empty() {
+            return Err(Error::Config("No thermostats match the selection. Run --status to refresh the list.".to_string()));
+        }
+        Ok(selected)
+    }
+}
+
+/// # matches(pattern: &str, thermostat: &ThermostatMeta) -> bool
+/// 
+/// Whether the pattern is the thermostat identifier, or glob-matches its name.
+pub fn matches(pattern: &str, thermostat: &ThermostatMeta) -> bool {
+    pattern == thermostat.identifier || glob_match(&pattern.to_lowercase(), &thermostat.name.to_lowercase())
+}
+
+/// # glob_match(pattern: &str, text: &str) -> bool
+/// 
+/// Match `*` (any run of characters, including none) and `?` (exactly one character).
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` seen, and the text position it was tried at, for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -1,227 +1,596 @@
-
-use log::{debug, error};
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
 use std::fs;
-use std::fs::File;
+use std::fs::{File, TryLockError};
 use std::io::Read;
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::str::FromStr;
 
+use crate::config::Config;
+use crate::ecobee::client::TokenStore;
 use crate::ecobee::models::{Tokens, ThermostatMeta};
-use crate::weather::models::{WeatherSettings};
+use crate::error::{Error, Result};
+use crate::secrets::SecretStore;
+use crate::selection::Groups;
+use crate::weather::models::WeatherSettings;
 
-static CONFIG_DIRECTORY: &str = ".bulk_ecobee_thermostat_control";
+static LEGACY_DIRECTORY: &str = ".bulk_ecobee_thermostat_control";
+static APP_DIRECTORY: &str = "bulk_ecobee_thermostat_control";
 static API_FILENAME: &str = "api_key";
+static CONFIG_FILENAME: &str = "config.yaml";
+static GROUPS_FILENAME: &str = "groups.yaml";
 static TOKENS_FILENAME: &str = "api_tokens";
+static TOKENS_LOCK_FILENAME: &str = "api_tokens.lock";
 static THERMOSTATS_FILENAME: &str = "thermostats.yaml";
 static WEATHER_FILENAME: &str = "weather.yaml";
+static PROFILES_DIRECTORY: &str = "profiles";
+
+pub static PROFILE_ENV: &str = "ECOBEE_PROFILE";
+pub static CONFIG_DIR_ENV: &str = "ECOBEE_CONFIG_DIR";
+
+/// Files shared by every profile. All other files are kept per profile (see `Location`),
+/// including groups.yaml, since groups name the thermostats of one account.
+static SHARED_FILENAMES: [&str; 1] = [CONFIG_FILENAME];
+
+/// Files kept in the state directory (see `get_state_base_path`). All other files are settings, kept in the configuration directory.
+static STATE_FILENAMES: [&str; 3] = [TOKENS_FILENAME, "api_tokens.enc", TOKENS_LOCK_FILENAME];
+
+/// Files holding secrets (the Ecobee app key, the tokens, and the WeatherAPI key), created readable by the owner only.
+/// (Including their encrypted versions, see `secrets::EncryptedFileSecretStore`.)
+static SECRET_FILENAMES: [&str; 5] = [API_FILENAME, TOKENS_FILENAME, WEATHER_FILENAME, "api_key.enc", "api_tokens.enc"];
+
+/// Current version of the tokens file format.
+const TOKENS_FILE_VERSION: u32 = 1;
+
+/// # TokensFile
+/// 
+/// The tokens file: a format version, then the `Tokens` fields.
+/// 
+/// version: 1
+/// access_token: ...
+/// refresh_token: ...
+/// issued_at: 2026-01-01T06:00:00Z
+/// expires_at: 2026-01-01T07:00:00Z
+#[derive(Deserialize, Serialize)]
+struct TokensFile {
+    version: u32,
+    #[serde(flatten)]
+    tokens: Tokens,
+}
+
+/// # Location
+/// 
+/// Where the files are kept: the configuration directory given with --config-dir or ECOBEE_CONFIG_DIR, if any
+/// (otherwise the XDG directories, see `get_config_base_path`), and the profile (None for the default profile).
+/// 
+/// Each profile is a separate Ecobee account: its api key, tokens, thermostats, groups and weather settings
+/// are kept in "profiles/<name>" under the configuration directory. The default profile keeps them in the directory itself,
+/// as before profiles existed. config.yaml is shared by all profiles.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    config_dir: Option<String>,
+    profile: Option<String>,
+}
+
+impl Location {
+
+    /// # new(config_dir: Option<String>) -> Location
+    /// 
+    /// The default profile, in `config_dir` if given, or else the directory in ECOBEE_CONFIG_DIR if set.
+    pub fn new(config_dir: Option<String>) -> Self {
+        Location {
+            config_dir: config_dir.map(|d| d.trim_end_matches('/').to_string()).or_else(|| env_dir(CONFIG_DIR_ENV)),
+            profile: None,
+        }
+    }
+
+    /// # with_profile(&self, profile: Option<String>) -> Result<Location>
+    /// 
+    /// The named profile (or the default profile, for None) in the same directory.
+    /// Names may only contain letters, digits, '-' and '_'.
+    pub fn with_profile(&self, profile: Option<String>) -> Result<Location> {
+        if let Some(name) = &profile {
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(Error::Config(format!("Bad profile name \"{name}\": use only letters, digits, '-' and '_'.")));
+            }
+        }
+        Ok(Location { config_dir: self.config_dir.clone(), profile })
+    }
 
-// NOTE: Functions in this file panic on error.
+    /// # profile(&self) -> Option<&str>
+    /// 
+    /// The profile (None for the default profile).
+    pub fn profile(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+}
 
-/// # create_config_dir
+/// # list_profiles(location: &Location) -> Result<Vec<Option<String>>>
 /// 
-/// Create the configuration directory if it doesn't already exist, or do nothing.
-pub fn create_config_dir() {
-    let dir = get_config_base_path();
-    match fs::create_dir_all(dir) {
-        Ok(_) => (),
-        Err(e) => panic!("Error creating config directory: {}", e)
+/// Every profile in the location's directory that has been set up (has an api key):
+/// the default profile (None) first, then the named profiles in order.
+pub fn list_profiles(location: &Location) -> Result<Vec<Option<String>>> {
+    let base = get_config_base_path(location)?;
+    let has_key = |dir: &str| [API_FILENAME, "api_key.enc"].iter().any(|f| std::path::Path::new(&format!("{dir}/{f}")).exists());
+    let mut profiles = vec![];
+    if has_key(&base) {
+        profiles.push(None);
     }
+    let mut names: Vec<String> = match fs::read_dir(format!("{base}/{PROFILES_DIRECTORY}")) {
+        Ok(entries) => entries.flatten()
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .filter(|name| has_key(&format!("{base}/{PROFILES_DIRECTORY}/{name}")))
+            .collect(),
+        Err(_) => vec![]
+    };
+    names.sort();
+    profiles.extend(names.into_iter().map(Some));
+    Ok(profiles)
 }
 
-/// # get_config_base_path
+/// # env_dir(var: &str) -> Option<String>
 /// 
-/// Return the base path for this application's configuration.
-fn get_config_base_path() -> String {
+/// A directory from an environment variable, ignoring empty and relative values (as the XDG spec asks).
+fn env_dir(var: &str) -> Option<String> {
+    std::env::var(var).ok()
+        .filter(|d| std::path::Path::new(d).is_absolute())
+        .map(|d| d.trim_end_matches('/').to_string())
+}
+
+/// # home_path(relative: &str) -> Result<String>
+fn home_path(relative: &str) -> Result<String> {
     match home::home_dir() {
         Some(path) => { 
             match path.as_path().to_str() {
-                Some(home_path) => format!("{}/{}", home_path, String::from_str(CONFIG_DIRECTORY).unwrap()),
-                None => panic!("Error converting home directory to string.")
+                Some(home_path) if !home_path.is_empty() => Ok(format!("{home_path}/{relative}")),
+                Some(_) => Err(Error::Config(format!("No home directory. Use --config-dir or {CONFIG_DIR_ENV}."))),
+                None => Err(Error::Config("Error converting home directory to string.".to_string()))
             }
         },
-        None => panic!("Error getting your home directory."),
+        None => Err(Error::Config(format!("Error getting your home directory. Use --config-dir or {CONFIG_DIR_ENV}."))),
     }
 }
 
-/// # get_config_file_path(filename: &str) -> String
+/// # create_config_dir(location: &Location) -> Result<()>
 /// 
-/// Get the absolute file path for the filename in the configuration directory
-fn get_config_file_path(filename: &str) -> String {
-    format!("{}/{}", get_config_base_path(), filename)
+/// Create the configuration and state directories (and the profile's directories) if they don't already exist, or do nothing.
+/// On unix they are created accessible by the owner only (0700).
+pub fn create_config_dir(location: &Location) -> Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for dir in [get_profile_path(location, &get_config_base_path(location)?), get_profile_path(location, &get_state_base_path(location)?)] {
+        builder.create(dir).map_err(|e| Error::Config(format!("Error creating config directory: {e}")))?;
+    }
+    Ok(())
 }
 
-
-/// # load_app_key() -> String
+/// # migrate_legacy_dir(location: &Location) -> Result<()>
 /// 
-/// Load the app/api key from local storage.
-pub fn load_app_key() -> String {
-    let mut file = match File::open(get_config_file_path(API_FILENAME)) {
-        Ok(f) => f,
-        Err(e) => { panic!("Error opening api key file: {e}"); }
+/// Move everything from the directory used by older versions ("~/.bulk_ecobee_thermostat_control") to the configuration and state directories,
+/// the first time this version runs (when neither of them exists yet). Nothing happens when the location has a directory
+/// (from --config-dir or ECOBEE_CONFIG_DIR), or when there is no legacy directory.
+/// 
+/// Tokens go to the state directory, everything else to the configuration directory, keeping the profiles layout.
+pub fn migrate_legacy_dir(location: &Location) -> Result<()> {
+    if location.config_dir.is_some() {
+        return Ok(());
+    }
+    let legacy = match home_path(LEGACY_DIRECTORY) {
+        Ok(legacy) if std::path::Path::new(&legacy).is_dir() => legacy,
+        _ => return Ok(())
     };
-    let mut contents = String::new();
-    
-    match file.read_to_string(&mut contents) {
-        Ok(_) => contents,
-        Err(e) => { panic!("Error reading ecobee_api_key file contents: {e}"); }
+    let (config, state) = (get_config_base_path(location)?, get_state_base_path(location)?);
+    if std::path::Path::new(&config).exists() || std::path::Path::new(&state).exists() {
+        return Ok(());
+    }
+    info!("Moving configuration from {legacy} to {config} and {state}.");
+    let mut dirs = vec![String::new()];
+    if let Ok(entries) = fs::read_dir(format!("{legacy}/{PROFILES_DIRECTORY}")) {
+        dirs.extend(entries.flatten()
+            .filter(|e| e.path().is_dir())
+            .filter_map(|e| e.file_name().to_str().map(|name| format!("/{PROFILES_DIRECTORY}/{name}"))));
+    }
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    let error = |e: std::io::Error| Error::Config(format!("Error moving configuration from {legacy}: {e}"));
+    for dir in &dirs {
+        builder.create(format!("{config}{dir}")).map_err(error)?;
+        builder.create(format!("{state}{dir}")).map_err(error)?;
+        for entry in fs::read_dir(format!("{legacy}{dir}")).map_err(error)?.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !entry.path().is_file() || name.ends_with(".tmp") {
+                continue;
+            }
+            if name == TOKENS_LOCK_FILENAME {
+                fs::remove_file(entry.path()).map_err(error)?;
+                continue;
+            }
+            let base = if STATE_FILENAMES.contains(&name.as_str()) { &state } else { &config };
+            let target = format!("{base}{dir}/{name}");
+            if fs::rename(entry.path(), &target).is_err() {
+                // Across filesystems: copy, then remove.
+                fs::copy(entry.path(), &target).map_err(error)?;
+                fs::remove_file(entry.path()).map_err(error)?;
+            }
+        }
+    }
+    for dir in dirs.iter().rev() {
+        let _ = fs::remove_dir(format!("{legacy}{dir}"));
     }
+    let _ = fs::remove_dir(format!("{legacy}/{PROFILES_DIRECTORY}"));
+    let _ = fs::remove_dir(&legacy);
+    Ok(())
 }
 
-/// # load_thermostats() -> Vec<ThermostatMeta>
+/// # check_permissions(location: &Location) -> Result<Vec<String>>
 /// 
-/// Load the thermostat metadata for all registered thermostats (identifier and name).
-pub fn load_thermostats() -> Vec<ThermostatMeta> {
-    let mut file = match File::open(get_config_file_path(THERMOSTATS_FILENAME)) {
-        Ok(f) => f,
-        Err(e) => { panic!("Error opening thermostats file: {e}"); }
-    };
-    let mut contents = String::new();
-    
-    let content = match file.read_to_string(&mut contents) {
-        Ok(_) => contents,
-        Err(e) => { panic!("Error reading thermostats contents: {e}"); }
-    };
+/// Describe every problem with the permissions of the configuration and state directories, the profile's directories, and the secret files in them:
+/// any access at all for group or other users. Empty if there are none (always, on platforms other than unix).
+pub fn check_permissions(location: &Location) -> Result<Vec<String>> {
+    let mut problems = vec![];
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let (config, state) = (get_config_base_path(location)?, get_state_base_path(location)?);
+        let mut dirs = vec![get_profile_path(location, &config), get_profile_path(location, &state)];
+        if location.profile.is_some() {
+            dirs.extend([config, state]);
+        }
+        dirs.dedup();
+        let files = SECRET_FILENAMES.iter().map(|f| get_config_file_path(location, f)).collect::<Result<Vec<String>>>()?;
+        let paths = dirs.into_iter().map(|d| (d, 0o700))
+            .chain(files.into_iter().map(|f| (f, 0o600)));
+        for (path, wanted) in paths {
+            if let Ok(metadata) = fs::metadata(&path) {
+                let mode = metadata.permissions().mode() & 0o777;
+                if mode & 0o077 != 0 {
+                    problems.push(format!("{path} is accessible by other users (mode {mode:o}, should be {wanted:o})"));
+                }
+            }
+        }
+    }
+    Ok(problems)
+}
 
-    let thermostats: Vec<ThermostatMeta> = serde_yaml::from_str(&content).unwrap();
+/// # get_config_base_path(location: &Location) -> Result<String>
+/// 
+/// Return the base path for this application's settings:
+/// the location's directory if it has one,
+/// otherwise "bulk_ecobee_thermostat_control" in $XDG_CONFIG_HOME (default "~/.config").
+fn get_config_base_path(location: &Location) -> Result<String> {
+    if let Some(dir) = &location.config_dir {
+        return Ok(dir.clone());
+    }
+    match env_dir("XDG_CONFIG_HOME") {
+        Some(xdg) => Ok(format!("{xdg}/{APP_DIRECTORY}")),
+        None => home_path(&format!(".config/{APP_DIRECTORY}"))
+    }
+}
 
-    thermostats
+/// # get_state_base_path(location: &Location) -> Result<String>
+/// 
+/// Return the base path for this application's state (the tokens):
+/// the location's directory if it has one,
+/// otherwise "bulk_ecobee_thermostat_control" in $XDG_STATE_HOME (default "~/.local/state").
+fn get_state_base_path(location: &Location) -> Result<String> {
+    if let Some(dir) = &location.config_dir {
+        return Ok(dir.clone());
+    }
+    match env_dir("XDG_STATE_HOME") {
+        Some(xdg) => Ok(format!("{xdg}/{APP_DIRECTORY}")),
+        None => home_path(&format!(".local/state/{APP_DIRECTORY}"))
+    }
 }
 
-/// # load_tokens() -> Tokens
+/// # get_profile_path(location: &Location, base: &str) -> String
 /// 
-/// Load access and refresh tokens (or just the initial access code and a blank string) from local storage.
-pub fn load_tokens() -> Tokens {
-    let mut file = match File::open(get_config_file_path(TOKENS_FILENAME)) {
-        Ok(f) => f,
-        Err(e) => { panic!("Error opening tokens file: {e}"); }
-    };
-    let mut contents = String::new();
-    
-    let content = match file.read_to_string(&mut contents) {
-        Ok(_) => contents,
-        Err(e) => { panic!("Error reading tokens file contents: {e}"); }
-    };
+/// Return the path under `base` for the profile's files (`base` itself for the default profile).
+fn get_profile_path(location: &Location, base: &str) -> String {
+    match &location.profile {
+        Some(name) => format!("{base}/{PROFILES_DIRECTORY}/{name}"),
+        None => base.to_string()
+    }
+}
 
-    let mut csplit = content.split('\n');
-    let access_token = csplit.next();
-    let refresh_token = csplit.next();
+/// # get_config_file_path(location: &Location, filename: &str) -> Result<String>
+/// 
+/// Get the absolute file path for the filename: in the state directory for tokens, otherwise the configuration directory,
+/// and in the profile's directory for per profile files.
+fn get_config_file_path(location: &Location, filename: &str) -> Result<String> {
+    let base = if STATE_FILENAMES.contains(&filename) { get_state_base_path(location)? } else { get_config_base_path(location)? };
+    if SHARED_FILENAMES.contains(&filename) {
+        Ok(format!("{base}/{filename}"))
+    } else {
+        Ok(format!("{}/{filename}", get_profile_path(location, &base)))
+    }
+}
+
+/// # config_file_exists(location: &Location, filename: &str) -> Result<bool>
+pub(crate) fn config_file_exists(location: &Location, filename: &str) -> Result<bool> {
+    Ok(std::path::Path::new(&get_config_file_path(location, filename)?).exists())
+}
 
-    Tokens { access_token: access_token.unwrap().to_string(), refresh_token: refresh_token.unwrap().to_string()}
+/// # remove_config_file(location: &Location, filename: &str, description: &str) -> Result<()>
+pub(crate) fn remove_config_file(location: &Location, filename: &str, description: &str) -> Result<()> {
+    fs::remove_file(get_config_file_path(location, filename)?)
+        .map_err(|e| Error::Config(format!("Error removing {description} file: {e}")))
 }
 
-/// # load_weather_settings() -> WeatherSettings
+/// # secret_store(location: &Location) -> Result<Box<dyn SecretStore>>
 /// 
-/// Load the thermostat metadata for all registered thermostats (identifier and name).
-pub fn load_weather_settings() -> WeatherSettings {
-    let mut file = match File::open(get_config_file_path(WEATHER_FILENAME)) {
-        Ok(f) => f,
-        Err(_) => { 
-            error!("Could not open weather settings file.");
-            return WeatherSettings{
-                api_key: None, 
-                query: None, 
-                metric: None,
-                heat_below: None, 
-                cool_above: None,
-                off_above: None,
-                off_below: None,
-                interval: None };
-        }
-    };
+/// The store for the app key and tokens, as selected by `secrets` in the config file (plain files by default).
+pub fn secret_store(location: &Location) -> Result<Box<dyn SecretStore>> {
+    load_config(location)?.secrets.unwrap_or_default().store()
+}
+
+/// # read_config_file(location: &Location, filename: &str, description: &str) -> Result<String>
+/// 
+/// Read the full contents of a file in the configuration directory.
+pub(crate) fn read_config_file(location: &Location, filename: &str, description: &str) -> Result<String> {
+    let mut file = File::open(get_config_file_path(location, filename)?)
+        .map_err(|e| Error::Config(format!("Error opening {description} file: {e}")))?;
     let mut contents = String::new();
-    
-    let content = match file.read_to_string(&mut contents) {
-        Ok(_) => contents,
-        Err(e) => { panic!("Error reading weather settings contents: {e}"); }
+    file.read_to_string(&mut contents)
+        .map_err(|e| Error::Config(format!("Error reading {description} file contents: {e}")))?;
+    Ok(contents)
+}
+
+/// # write_config_file(location: &Location, filename: &str, description: &str, contents: &str) -> Result<()>
+/// 
+/// Replace the contents of a file in the configuration directory.
+/// 
+/// The contents are written to a temporary file which is then renamed over the original,
+/// so a crash mid-write leaves either the old or the new file, never a truncated one.
+/// Secret files (see `SECRET_FILENAMES`) are created readable and writable by the owner only (0600, on unix).
+pub(crate) fn write_config_file(location: &Location, filename: &str, description: &str, contents: &str) -> Result<()> {
+    let path = get_config_file_path(location, filename)?;
+    let temp_path = format!("{path}.{}.tmp", std::process::id());
+    let write = || -> std::io::Result<()> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        if SECRET_FILENAMES.contains(&filename) {
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        }
+        let mut file = options.open(&temp_path)?;
+        write!(file, "{contents}")?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)
     };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        Error::Config(format!("Error writing {description}: {e}"))
+    })
+}
 
-    let weather_settings: WeatherSettings = serde_yaml::from_str(&content).unwrap();
+/// # load_app_key(location: &Location) -> Result<String>
+/// 
+/// Load the app/api key from local storage.
+pub fn load_app_key(location: &Location) -> Result<String> {
+    secret_store(location)?.read(location, API_FILENAME, "api key")
+}
 
-    weather_settings
+/// # load_config(location: &Location) -> Result<Config>
+/// 
+/// Load the general settings, or defaults if there is no config file.
+pub fn load_config(location: &Location) -> Result<Config> {
+    let path = get_config_file_path(location, CONFIG_FILENAME)?;
+    if !std::path::Path::new(&path).exists() {
+        return Ok(Config::default());
+    }
+    let content = read_config_file(location, CONFIG_FILENAME, "config")?;
+    serde_yaml::from_str(&content).map_err(|e| Error::Config(format!("Error parsing config file: {e}")))
 }
 
+/// # load_groups(location: &Location) -> Result<Groups>
+/// 
+/// Load the user-defined thermostat groups, or none if there is no groups file.
+pub fn load_groups(location: &Location) -> Result<Groups> {
+    let path = get_config_file_path(location, GROUPS_FILENAME)?;
+    if !std::path::Path::new(&path).exists() {
+        return Ok(Groups::new());
+    }
+    let content = read_config_file(location, GROUPS_FILENAME, "groups")?;
+    serde_yaml::from_str(&content).map_err(|e| Error::Config(format!("Error parsing groups file: {e}")))
+}
 
-/// # write_api_key(api_key: String)
+/// # load_thermostats(location: &Location) -> Result<Vec<ThermostatMeta>>
 /// 
-/// Write the api_key entered by the user into local storage.
-pub fn write_api_key(api_key: String) {
+/// Load the thermostat metadata for all registered thermostats (identifier and name).
+pub fn load_thermostats(location: &Location) -> Result<Vec<ThermostatMeta>> {
+    let content = read_config_file(location, THERMOSTATS_FILENAME, "thermostats")?;
+    serde_yaml::from_str(&content).map_err(|e| Error::Config(format!("Error parsing thermostats file: {e}")))
+}
 
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(get_config_file_path(API_FILENAME));
-    match file {
-        Ok(mut f) => {
-            match write!(f, "{api_key}") {
-                Ok(_) => debug!("Successfully wrote api key."),
-                Err(e) => panic!("Error writing api key {:?}", e.to_string())
-            }
-        },
-        Err(e) => panic!("Error writing api key {:?}", e.to_string())
+/// # load_tokens(location: &Location) -> Result<Tokens>
+/// 
+/// Load access and refresh tokens (or just the initial access code and a blank string) from local storage,
+/// along with their issue and expiry times.
+/// 
+/// Files in the legacy format (see `parse_legacy_tokens`) are read, then rewritten in the current format.
+/// The rewrite happens under the tokens lock, after reading the file again, so it can't overwrite tokens another process
+/// has just refreshed. If the lock is held (by another process, or by this one while refreshing) the rewrite is left
+/// for later: the next write is in the current format anyway.
+pub fn load_tokens(location: &Location) -> Result<Tokens> {
+    let content = secret_store(location)?.read(location, TOKENS_FILENAME, "tokens")?;
+    if let Some(tokens) = parse_tokens(&content)? {
+        return Ok(tokens);
+    }
+    let Some(_lock) = try_lock_tokens(location)? else {
+        debug!("Tokens are locked, leaving the tokens file migration for later.");
+        return parse_legacy_tokens(&content);
+    };
+    let content = secret_store(location)?.read(location, TOKENS_FILENAME, "tokens")?;
+    if let Some(tokens) = parse_tokens(&content)? {
+        return Ok(tokens);
     }
+    let tokens = parse_legacy_tokens(&content)?;
+    info!("Migrating tokens file to version {TOKENS_FILE_VERSION}.");
+    write_tokens(location, &tokens)?;
+    Ok(tokens)
 }
 
+/// # parse_tokens(content: &str) -> Result<Option<Tokens>>
+/// 
+/// Parse a tokens file in the current format, or None if it is in the legacy format.
+fn parse_tokens(content: &str) -> Result<Option<Tokens>> {
+    let document: serde_yaml::Value = serde_yaml::from_str(content).unwrap_or(serde_yaml::Value::Null);
+    if !document.is_mapping() {
+        return Ok(None);
+    }
+    let file: TokensFile = serde_yaml::from_value(document)
+        .map_err(|e| Error::Token(format!("Error parsing tokens file: {e}")))?;
+    if file.version > TOKENS_FILE_VERSION {
+        return Err(Error::Token(format!("Tokens file version {} was written by a newer version of this tool.", file.version)));
+    }
+    Ok(Some(file.tokens))
+}
 
-/// # write_thermostats(thermostats: Vec<ThermostatMeta>)
+/// # parse_legacy_tokens(content: &str) -> Result<Tokens>
 /// 
-/// Write the thermostat metadata into local storage for use during updates.
-pub fn write_thermostats(thermostats: Vec<ThermostatMeta>) {
+/// Parse the legacy tokens file: access token and refresh token lines,
+/// optionally followed by issue and expiry time (RFC 3339) lines.
+fn parse_legacy_tokens(content: &str) -> Result<Tokens> {
+    let mut csplit = content.split('\n');
+    match (csplit.next(), csplit.next()) {
+        (Some(access_token), Some(refresh_token)) => Ok(Tokens {
+            access_token: access_token.to_string(),
+            refresh_token: refresh_token.to_string(),
+            issued_at: parse_time(csplit.next())?,
+            expires_at: parse_time(csplit.next())?
+        }),
+        _ => Err(Error::Token("Tokens file is incomplete. Run --pin and --auth again.".to_string()))
+    }
+}
 
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(get_config_file_path(THERMOSTATS_FILENAME));
-    match file {
-        Ok(mut f) => {
-            match write!(f, "{}", serde_yaml::to_string(&thermostats).unwrap()) {
-                Ok(_) => (),
-                Err(e) => panic!("Error writing thermostat metadata {:?}", e.to_string())
-            }
-        },
-        Err(e) => panic!("Error writing thermostat metadata {:?}", e.to_string())
+/// # parse_time(line: Option<&str>) -> Result<Option<DateTime<Utc>>>
+/// 
+/// Parse an optional RFC 3339 timestamp line from the legacy tokens file (a missing or blank line is None).
+fn parse_time(line: Option<&str>) -> Result<Option<DateTime<Utc>>> {
+    match line.map(str::trim).filter(|l| !l.is_empty()) {
+        Some(l) => DateTime::parse_from_rfc3339(l)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|e| Error::Token(format!("Bad timestamp in tokens file ({l}): {e}"))),
+        None => Ok(None)
     }
 }
 
-/// # write_tokens(access_token: String, refresh_token: String)
+/// # load_weather_settings(location: &Location) -> Result<WeatherSettings>
 /// 
-/// Write the updated access and refresh tokens to local storage.
-pub fn write_tokens(access_token: String, refresh_token: String) {
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .truncate(true)
-        .open(get_config_file_path(TOKENS_FILENAME));
-    match file {
-        Ok(mut f) => {
-            match write!(f, "{access_token}\n{refresh_token}") {
-                Ok(_) => (),
-                Err(e) => panic!("Error writing access tokens {:?}", e.to_string())
-            }
-        },
-        Err(e) => panic!("Error writing access tokens {:?}", e.to_string())
+/// Load the weather settings, or empty settings if none have been saved yet.
+pub fn load_weather_settings(location: &Location) -> Result<WeatherSettings> {
+    let path = get_config_file_path(location, WEATHER_FILENAME)?;
+    if !std::path::Path::new(&path).exists() {
+        debug!("No weather settings file found.");
+        return Ok(WeatherSettings::default());
     }
+    let content = read_config_file(location, WEATHER_FILENAME, "weather settings")?;
+    serde_yaml::from_str(&content).map_err(|e| Error::Config(format!("Error parsing weather settings: {e}")))
+}
+
+
+/// # write_api_key(location: &Location, api_key: String) -> Result<()>
+/// 
+/// Write the api_key entered by the user into local storage.
+pub fn write_api_key(location: &Location, api_key: String) -> Result<()> {
+    secret_store(location)?.write(location, API_FILENAME, "api key", &api_key)?;
+    debug!("Successfully wrote api key.");
+    Ok(())
 }
 
-/// # write_weather_settings(weather_settings: WeatherSettings)
+
+/// # write_thermostats(location: &Location, thermostats: Vec<ThermostatMeta>) -> Result<()>
 /// 
 /// Write the thermostat metadata into local storage for use during updates.
-pub fn write_weather_settings(weather_settings: WeatherSettings) {
+pub fn write_thermostats(location: &Location, thermostats: Vec<ThermostatMeta>) -> Result<()> {
+    let content = serde_yaml::to_string(&thermostats)
+        .map_err(|e| Error::Config(format!("Error serializing thermostat metadata: {e}")))?;
+    write_config_file(location, THERMOSTATS_FILENAME, "thermostat metadata", &content)
+}
 
-    let file = OpenOptions::new()
+/// # write_tokens(location: &Location, tokens: &Tokens) -> Result<()>
+/// 
+/// Write the updated access and refresh tokens, and their issue and expiry times, to local storage.
+pub fn write_tokens(location: &Location, tokens: &Tokens) -> Result<()> {
+    let content = serde_yaml::to_string(&TokensFile { version: TOKENS_FILE_VERSION, tokens: tokens.clone() })
+        .map_err(|e| Error::Config(format!("Error serializing tokens: {e}")))?;
+    secret_store(location)?.write(location, TOKENS_FILENAME, "access tokens", &content)
+}
+
+/// # lock_tokens(location: &Location) -> Result<File>
+/// 
+/// Take an exclusive advisory lock on the tokens lock file, waiting for any other process holding it.
+/// The lock is released when the returned file is closed (dropped).
+pub fn lock_tokens(location: &Location) -> Result<File> {
+    let file = open_tokens_lock(location)?;
+    file.lock().map_err(|e| Error::Config(format!("Error locking tokens: {e}")))?;
+    Ok(file)
+}
+
+/// # try_lock_tokens(location: &Location) -> Result<Option<File>>
+/// 
+/// Like `lock_tokens`, but without waiting: None if the lock is held elsewhere (including through another open
+/// lock file in this process).
+pub fn try_lock_tokens(location: &Location) -> Result<Option<File>> {
+    let file = open_tokens_lock(location)?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(Error::Config(format!("Error locking tokens: {e}")))
+    }
+}
+
+/// # open_tokens_lock(location: &Location) -> Result<File>
+fn open_tokens_lock(location: &Location) -> Result<File> {
+    OpenOptions::new()
         .write(true)
         .create(true)
-        .truncate(true)
-        .open(get_config_file_path(WEATHER_FILENAME));
-    match file {
-        Ok(mut f) => {
-            match write!(f, "{}", serde_yaml::to_string(&weather_settings).unwrap()) {
-                Ok(_) => (),
-                Err(e) => panic!("Error writing thermostat metadata {:?}", e.to_string())
-            }
-        },
-        Err(e) => panic!("Error writing thermostat metadata {:?}", e.to_string())
+        .truncate(false)
+        .open(get_config_file_path(location, TOKENS_LOCK_FILENAME)?)
+        .map_err(|e| Error::Config(format!("Error opening tokens lock file: {e}")))
+}
+
+/// # write_weather_settings(location: &Location, weather_settings: WeatherSettings) -> Result<()>
+/// 
+/// Write the weather settings into local storage for use by weather mode.
+pub fn write_weather_settings(location: &Location, weather_settings: WeatherSettings) -> Result<()> {
+    let content = serde_yaml::to_string(&weather_settings)
+        .map_err(|e| Error::Config(format!("Error serializing weather settings: {e}")))?;
+    write_config_file(location, WEATHER_FILENAME, "weather settings", &content)
+}
+
+/// # FileTokenStore
+/// 
+/// `TokenStore` backed by the tokens file of a profile (see `Location`).
+pub struct FileTokenStore {
+    location: Location,
+}
+
+impl FileTokenStore {
+
+    /// # new(location: Location) -> FileTokenStore
+    pub fn new(location: Location) -> Self {
+        FileTokenStore { location }
     }
-}
\ No newline at end of file
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self) -> Result<Tokens> {
+        load_tokens(&self.location)
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<()> {
+        write_tokens(&self.location, tokens)
+    }
+
+    fn lock(&self) -> Result<Box<dyn Any>> {
+        Ok(Box::new(lock_tokens(&self.location)?))
+    }
+}
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// # TemperatureUnit
+/// 
+/// The unit temperatures are entered and displayed in.
+/// The Ecobee API always uses tenths of a degree Fahrenheit (eg 715 for 71.5 F).
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all="lowercase")]
+pub enum TemperatureUnit {
+    #[default]
+    Fahrenheit,
+    Celsius,
+}
+
+impl TemperatureUnit {
+
+    /// # from_metric(metric: Option<bool>) -> TemperatureUnit
+    /// 
+    /// Celsius if metric is set to true, otherwise Fahrenheit.
+    pub fn from_metric(metric: Option<bool>) -> Self {
+        if metric.unwrap_or(false) { TemperatureUnit::Celsius } else { TemperatureUnit::Fahrenheit }
+    }
+
+    /// # to_ecobee(&self, degrees: f64) -> i64
+    /// 
+    /// Convert degrees in this unit to Ecobee tenths of a degree Fahrenheit.
+    pub fn to_ecobee(&self, degrees: f64) -> i64 {
+        let fahrenheit = match self {
+            TemperatureUnit::Fahrenheit => degrees,
+            TemperatureUnit::Celsius => degrees * 9.0 / 5.0 + 32.0,
+        };
+        (fahrenheit * 10.0).round() as i64
+    }
+
+    /// # from_ecobee(&self, tenths: f64) -> f64
+    /// 
+    /// Convert Ecobee tenths of a degree Fahrenheit to degrees in this unit (rounded to a tenth).
+    pub fn from_ecobee(&self, tenths: f64) -> f64 {
+        let fahrenheit = tenths / 10.0;
+        match self {
+            TemperatureUnit::Fahrenheit => fahrenheit,
+            TemperatureUnit::Celsius => ((fahrenheit - 32.0) * 5.0 / 9.0 * 10.0).round() / 10.0,
+        }
+    }
+
+    /// # symbol(&self) -> &str
+    /// 
+    /// The unit symbol, for display (eg "71.5°F").
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Celsius => "°C",
+        }
+    }
+}
@@ -1,103 +1,192 @@
 use chrono;
-use core::panic;
 use log::{info, error};
 use std::{thread, time};
 use ureq;
-use std::error::Error;
-use crate::storage;
-use crate::ecobee;
+use crate::config::Config;
+use crate::ecobee::client::EcobeeClient;
+use crate::ecobee::models::{HvacMode, HvacModeStatus, SensorReading, StatusResponseThermostat, ThermostatMeta, ThermostatSummary};
+use crate::error::{Error, Result};
+use crate::http;
+use crate::selection::ResolvedSelection;
+use crate::storage::{self, Location};
+use crate::units::TemperatureUnit;
 use crate::weather::models;
 
-use super::models::WeatherSettings;
+use super::models::{Conditions, WeatherSettings};
 
-/// # get_temp()
+/// # get_temp(weather_settings: &WeatherSettings, config: &Config) -> Result<f64>
 /// 
-/// Get the temperature using weather api.
-fn get_temp(weather_settings: &WeatherSettings) -> Result<f64, Box<dyn Error>> {
-    ecobee::api::refresh_tokens();
-    let response = ureq::post("https://api.weatherapi.com/v1/current.json")
-    .query("key", weather_settings.api_key.as_ref().unwrap().as_str())
-    .query("q", weather_settings.query.as_ref().unwrap().as_str())
-    .call()?;
-    let json_response = response.into_json::<models::WeatherResponse>()?;            
-    let temp: Option<f64> = if weather_settings.metric.unwrap() {
-        json_response.current.temp_c
-    } else { json_response.current.temp_f };
+/// Get the temperature using weather api, in the configured unit (see `Config::temperature_unit`),
+/// which is also the unit the weather thresholds are compared in.
+/// The request goes to the configured WeatherAPI base URL, retried according to the configured retry policy.
+pub fn get_temp(weather_settings: &WeatherSettings, config: &Config) -> Result<f64> {
+    let (api_key, query) = match (weather_settings.api_key.as_ref(), weather_settings.query.as_ref()) {
+        (Some(api_key), Some(query)) => (api_key, query),
+        _ => return Err(Error::Config("Weather API key or query is not set. Run --weather-setup before proceeding.".to_string()))
+    };
+    let response = http::call(&config.retry_policy(), ureq::post(&format!("{}/v1/current.json", config.weather_base_url()))
+    .query("key", api_key.as_str())
+    .query("q", query.as_str()))?;
+    let json_response = response.into_json::<models::WeatherResponse>()
+        .map_err(|e| Error::Json(e.to_string()))?;
+    let temp: Option<f64> = match config.temperature_unit(weather_settings.metric) {
+        TemperatureUnit::Celsius => json_response.current.temp_c,
+        TemperatureUnit::Fahrenheit => json_response.current.temp_f,
+    };
     match temp {
         Some(t) => Ok(t),
-        _ => Err("No temperature found! Something went wrong with the temperature pulled from WeatherAPI: {temp:?}")?
+        _ => Err(Error::Json("No temperature found in the response from WeatherAPI.".to_string()))
     }
 }
 
-/// # check()
+/// # check(location: &Location, config: &Config, quiet: bool) -> Result<()>
 /// 
-/// Check the weather using weather api.
+/// Check the weather using weather api. The result is printed, or logged at info level if `quiet`
+/// (leaving stdout to a json or yaml document).
 /// 
 /// (May differ from other sources - eg check against weather.com)
-pub fn check() {
-    let weather_settings = storage::load_weather_settings();
-    let temp = get_temp(&weather_settings);
-    match temp {
-        Ok(t) => { 
-            let timestamp = chrono::offset::Local::now().to_rfc2822();
-            println!("Current temp is {t} as of {timestamp}");
-        },
-        Err(e) => error!("{:?}", e)
+pub fn check(location: &Location, config: &Config, quiet: bool) -> Result<()> {
+    let weather_settings = storage::load_weather_settings(location)?;
+    let t = get_temp(&weather_settings, config)?;
+    let unit = config.temperature_unit(weather_settings.metric);
+    let timestamp = chrono::offset::Local::now().to_rfc2822();
+    let message = format!("Current temp is {t}{} as of {timestamp}", unit.symbol());
+    if quiet {
+        info!("{message}");
+    } else {
+        println!("{message}");
     }
+    Ok(())
 }
 
-/// # set_hvac()
+/// # set_hvac(client, thermostats, mode: HvacMode, temp: f64, timestamp: String, hvac_mode: &HvacModeStatus) -> Result<()>
 /// 
 /// Set the hvac mode, log to info the current temp and mode first, along with the change timestamp.
-fn set_hvac<'a>(mode: &'a str, temp: f64, timestamp: String, hvac_mode: &'a str) -> &'a str {
+fn set_hvac(client: &mut EcobeeClient, thermostats: &[ThermostatMeta], mode: HvacMode, temp: f64, timestamp: String, hvac_mode: &HvacModeStatus) -> Result<()> {
     info!("Current temp: {temp} current mode: {hvac_mode} - change @ {timestamp}");
-    ecobee::api::refresh_tokens();
-    ecobee::api::update_thermostats(mode);
-    mode
+    client.update_thermostats(thermostats, mode)?.into_result()
 }
 
-/// # run()
+/// # target_mode(weather_settings: &WeatherSettings, temp: f64) -> Option<HvacMode>
 /// 
-/// Run weather mode in an infinite loop (until broken by user input).
+/// The mode called for by the outside temperature, or None if it is between thresholds (leave the mode alone).
+/// 
+/// Above the thresholds, cooling takes priority over turning off. Below them, heating takes priority over turning off.
+pub fn target_mode(weather_settings: &WeatherSettings, temp: f64) -> Option<HvacMode> {
+    if weather_settings.cool_above.is_some_and(|c| temp > c) {
+        Some(HvacMode::Cool)
+    }
+    else if weather_settings.off_above.is_some_and(|o| temp > o) {
+        Some(HvacMode::Off)
+    }
+    else if weather_settings.heat_below.is_some_and(|h| temp < h) {
+        Some(HvacMode::Heat)
+    }
+    else if weather_settings.off_below.is_some_and(|o| temp < o) {
+        Some(HvacMode::Off)
+    } else { None }
+}
+
+/// # decide(weather_settings: &WeatherSettings, conditions: &Conditions) -> Option<HvacMode>
 /// 
-/// Apply a change if the temperature is above or below thresholds specified during weather setup, otherwise continue doing nothing.
-pub fn run() {
-    let weather_settings = storage::load_weather_settings();
-    if weather_settings.interval.is_none() {
-        panic!("Interval is not set. Run --weathersetup before proceeding.");
+/// The mode to change to, if any: the `target_mode` for the outdoor temperature, unless `occupied_only` is set
+/// and the sensors show nobody there. (Sensors that can't tell don't hold a change back.)
+pub fn decide(weather_settings: &WeatherSettings, conditions: &Conditions) -> Option<HvacMode> {
+    let mode = target_mode(weather_settings, conditions.outdoor)?;
+    if weather_settings.occupied_only.unwrap_or(false) && conditions.occupied() == Some(false) {
+        info!("Leaving the mode alone (would be {mode}): no sensor detects occupancy.");
+        return None;
+    }
+    Some(mode)
+}
+
+/// # refresh(client: &mut EcobeeClient, thermostats: &[ThermostatMeta], summary: &mut ThermostatSummary, unit: TemperatureUnit, occupied_only: bool) -> Result<Option<(HvacModeStatus, Vec<SensorReading>)>>
+/// 
+/// Check the thermostat summary, and only if one of `thermostats` has changed since `summary` read back their HVAC Modes.
+/// Catches changes made elsewhere (eg on the thermostat or in the app) without polling the /thermostat endpoint.
+/// 
+/// Sensor readings are only needed for `occupied_only`: then new runtime values (which change every few minutes) also count
+/// as a change, and the readings are read back too. Otherwise they are left out and the readings returned are empty.
+/// 
+/// Returns the new mode status and readings, or None if nothing changed. `summary` is replaced with the latest one.
+pub fn refresh(client: &mut EcobeeClient, thermostats: &[ThermostatMeta], summary: &mut ThermostatSummary, unit: TemperatureUnit, occupied_only: bool) -> Result<Option<(HvacModeStatus, Vec<SensorReading>)>> {
+    let latest = client.thermostat_summary()?;
+    let changed = {
+        let mut changed = latest.changed_since(summary);
+        if occupied_only {
+            changed.extend(latest.runtime_changed_since(summary));
+        }
+        thermostats.iter().any(|t| changed.contains(&t.identifier.as_str()))
+    };
+    *summary = latest;
+    if !changed {
+        return Ok(None);
     }
-    let duration = time::Duration::from_secs(weather_settings.interval.unwrap() * 60);
-    ecobee::api::refresh_tokens();
-    let binding = ecobee::api::thermostat_status(); // This will either return a consistent mode (heat, cool, off) or "inconsistent".
-    let mut hvac_mode = binding.as_str();
+    let details = client.thermostat_details(thermostats, occupied_only)?;
+    Ok(Some((HvacModeStatus::of(&details), sensor_readings(&details, unit))))
+}
+
+/// # sensor_readings(thermostats: &[StatusResponseThermostat], unit: TemperatureUnit) -> Vec<SensorReading>
+fn sensor_readings(thermostats: &[StatusResponseThermostat], unit: TemperatureUnit) -> Vec<SensorReading> {
+    thermostats.iter().flat_map(|t| t.remote_sensors.iter().map(|sensor| sensor.reading(unit))).collect()
+}
+
+/// # run(client: &mut EcobeeClient, location: &Location, config: &Config, selection: &ResolvedSelection) -> Result<()>
+/// 
+/// Run weather mode in an infinite loop (until broken by user input).
+/// 
+/// Changes apply to the selected thermostats out of those returned by the initial status call.
+/// Before each check the thermostat summary is consulted, so that modes changed elsewhere, and new sensor readings, are noticed (see `refresh`).
+/// 
+/// Apply a change if the temperature is above or below thresholds specified during weather setup, otherwise continue doing nothing
+/// (see `decide`).
+/// Errors inside the loop are logged and retried on the next interval; only setup errors are returned.
+pub fn run(client: &mut EcobeeClient, location: &Location, config: &Config, selection: &ResolvedSelection) -> Result<()> {
+    let weather_settings = storage::load_weather_settings(location)?;
+    let interval = match weather_settings.interval {
+        Some(interval) => interval,
+        None => return Err(Error::Config("Interval is not set. Run --weather-setup before proceeding.".to_string()))
+    };
+    let duration = time::Duration::from_secs(interval * 60);
+    let unit = config.temperature_unit(weather_settings.metric);
+    let occupied_only = weather_settings.occupied_only.unwrap_or(false);
+    let mut summary = client.thermostat_summary()?;
+    let mut status = client.thermostat_status()?;
+    status.thermostats.retain(|t| selection.includes(&t.meta()));
+    let thermostats = selection.apply(status.metas())?;
+    let mut hvac_mode = HvacModeStatus::of(&status.thermostats);
+    let mut sensors = sensor_readings(&status.thermostats, unit);
     let initial_timestamp = chrono::offset::Local::now().to_rfc2822();
     let duration_minutes = duration.as_secs() / 60;
-    println!("Initializing weather loop @ {initial_timestamp} checking every {duration_minutes} minutes.\nCurrent hvac mode is {hvac_mode}");
+    client.progress(format!("Initializing weather loop @ {initial_timestamp} checking every {duration_minutes} minutes.\nCurrent hvac mode is {hvac_mode}"));
     loop {
-        let temp = get_temp(&weather_settings);
+        match refresh(client, &thermostats, &mut summary, unit, occupied_only) {
+            Ok(Some((current, readings))) => {
+                if current != hvac_mode {
+                    info!("Hvac mode changed to {current}");
+                }
+                hvac_mode = current;
+                sensors = readings;
+            },
+            Ok(None) => (),
+            Err(e) => error!("Error checking thermostat summary: {e}")
+        }
+        let temp = get_temp(&weather_settings, config);
         match temp {
             Ok(t) => { 
                 let timestamp = chrono::offset::Local::now().to_rfc2822();
                 info!("Checking temp ({t}) @ {timestamp}");
 
-                // If the temp is above our thresholds, prioritize cooling, otherwise turn off, otherwise leave alone.
-                if weather_settings.cool_above.is_some() && t > weather_settings.cool_above.unwrap() && hvac_mode != "cool" {
-                    hvac_mode = set_hvac("cool", t, timestamp, hvac_mode);
-                }
-                else if weather_settings.off_above.is_some() && t > weather_settings.off_above.unwrap() && hvac_mode != "off" {
-                    hvac_mode = set_hvac("off", t, timestamp, hvac_mode);
-                }
-                
-                // If the temp is below our thresholds, prioritize heating, otherwise turn off, otherwise leave alone.
-                else if weather_settings.heat_below.is_some() && t < weather_settings.heat_below.unwrap() && hvac_mode != "heat" {
-                    hvac_mode = set_hvac("heat", t, timestamp, hvac_mode);
-                }
-                else if weather_settings.off_below.is_some() && t < weather_settings.off_below.unwrap() && hvac_mode != "off" {
-                    hvac_mode = set_hvac("off", t, timestamp, hvac_mode);
+                let conditions = Conditions { outdoor: t, sensors: sensors.clone() };
+                if let Some(mode) = decide(&weather_settings, &conditions).filter(|m| hvac_mode.uniform() != Some(*m)) {
+                    match set_hvac(client, &thermostats, mode, t, timestamp, &hvac_mode) {
+                        Ok(()) => hvac_mode = HvacModeStatus::Uniform(mode),
+                        Err(e) => error!("Error changing hvac mode to {mode}: {e}")
+                    }
                 }
             },
-            Err(e) => error!("{:?}", e)
+            Err(e) => error!("{e}")
         }
         thread::sleep(duration);
     }
-}
\ No newline at end of file
+}
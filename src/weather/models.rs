@@ -1,6 +1,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::ecobee::models::SensorReading;
+
 #[derive(Deserialize, Debug)]
 pub struct WeatherResponse {
 
@@ -17,7 +19,7 @@ pub struct WeatherResponseCurrent {
 /// # WeatherSettings
 /// 
 /// For storing/retrieving Weather API settings and usage settings for Weather Mode.
-#[derive(Deserialize, Debug, Serialize)]
+#[derive(Default, Deserialize, Debug, Serialize)]
 pub struct WeatherSettings {
 
     pub api_key: Option<String>,
@@ -27,5 +29,29 @@ pub struct WeatherSettings {
     pub cool_above: Option<f64>,
     pub off_above: Option<f64>,
     pub off_below: Option<f64>,
-    pub interval: Option<u64>
+    pub interval: Option<u64>,
+    pub occupied_only: Option<bool>
+}
+
+/// # Conditions
+/// 
+/// What weather mode decides on.
+/// 
+/// outdoor  The outdoor temperature, in the configured unit.
+/// sensors  The latest readings from every sensor of the selected thermostats.
+#[derive(Clone, Debug, Default)]
+pub struct Conditions {
+    pub outdoor: f64,
+    pub sensors: Vec<SensorReading>,
+}
+
+impl Conditions {
+
+    /// # occupied(&self) -> Option<bool>
+    /// 
+    /// Whether any sensor reports someone there, or None if none of them can tell.
+    pub fn occupied(&self) -> Option<bool> {
+        let readings: Vec<bool> = self.sensors.iter().filter_map(|s| s.occupied).collect();
+        if readings.is_empty() { None } else { Some(readings.contains(&true)) }
+    }
 }
\ No newline at end of file
@@ -1,73 +1,120 @@
-use log::error;
-use std::io::{self, Write};
+use std::fs;
 use std::str::FromStr;
-use crate::storage;
-use crate::weather::models;
+use crate::error::{Error, Result};
+use crate::prompt;
+use crate::storage::{self, Location};
+use crate::weather::models::WeatherSettings;
 
+/// Setting names accepted by `set` (the field names in weather.yaml).
+pub static SETTING_NAMES: [&str; 9] = ["api_key", "query", "metric", "cool_above", "heat_below", "off_above", "off_below", "interval", "occupied_only"];
 
-/// # setup()
+/// # setup(location: &Location) -> Result<()>
 /// 
 /// User entered settings for weather mode, including API credentials.
-pub fn setup() {
+pub fn setup(location: &Location) -> Result<()> {
     println!("Weather setup.");
     println!("Press <ENTER> to skip an entry and keep the current value.");
     println!("Press <SPACE> then <ENTER> to set an entry to empty (unset).");
-    let weather_settings = storage::load_weather_settings();
-    let api_key = get_value::<String>("weatherapi.com API Key", weather_settings.api_key);
-    let query = get_value::<String>("query", weather_settings.query);
-    let metric = get_value::<bool>("use metric?", weather_settings.metric);
-    let cool_above = get_value::<f64>("cool above", weather_settings.cool_above);
-    let heat_below = get_value::<f64>("heat below", weather_settings.heat_below);
-    let off_above = get_value::<f64>("turn hvac off above", weather_settings.off_above);
-    let off_below = get_value::<f64>("turn hvac off below", weather_settings.off_below);
-    let interval =  get_value::<u64>("interval in minutes", weather_settings.interval);
+    let weather_settings = storage::load_weather_settings(location)?;
+    save(location, WeatherSettings {
+        api_key: get_value::<String>("weatherapi.com API Key", weather_settings.api_key)?,
+        query: get_value::<String>("query", weather_settings.query)?,
+        metric: get_value::<bool>("use metric?", weather_settings.metric)?,
+        cool_above: get_value::<f64>("cool above", weather_settings.cool_above)?,
+        heat_below: get_value::<f64>("heat below", weather_settings.heat_below)?,
+        off_above: get_value::<f64>("turn hvac off above", weather_settings.off_above)?,
+        off_below: get_value::<f64>("turn hvac off below", weather_settings.off_below)?,
+        interval: get_value::<u64>("interval in minutes", weather_settings.interval)?,
+        occupied_only: get_value::<bool>("only change modes while a sensor detects occupancy?", weather_settings.occupied_only)?,
+    })
+}
 
-    if !validate(&api_key, &query, &cool_above, &heat_below, &off_above, &off_below, &interval) {
-        return
+/// # set(location: &Location, assignments: &[String]) -> Result<()>
+/// 
+/// Change the weather settings non-interactively, from "name=value" assignments (see `SETTING_NAMES`).
+/// An empty value unsets the setting. Settings not mentioned keep their current values.
+/// The result is validated before it is saved.
+pub fn set(location: &Location, assignments: &[String]) -> Result<()> {
+    let mut weather_settings = storage::load_weather_settings(location)?;
+    for assignment in assignments {
+        let (name, value) = assignment.split_once('=')
+            .ok_or_else(|| Error::Config(format!("Expected name=value, got \"{assignment}\".")))?;
+        apply(&mut weather_settings, name.trim(), value.trim())?;
+    }
+    save(location, weather_settings)
+}
+
+/// # import(location: &Location, path: &str) -> Result<()>
+/// 
+/// Replace the weather settings with those in a YAML file (in the same format as weather.yaml), after validating them.
+pub fn import(location: &Location, path: &str) -> Result<()> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("Error reading weather settings from {path}: {e}")))?;
+    let weather_settings: WeatherSettings = serde_yaml::from_str(&content)
+        .map_err(|e| Error::Config(format!("Error parsing weather settings from {path}: {e}")))?;
+    save(location, weather_settings)
+}
+
+/// # apply(weather_settings: &mut WeatherSettings, name: &str, value: &str) -> Result<()>
+/// 
+/// Set one setting by name from its string value (an empty value unsets it).
+pub fn apply(weather_settings: &mut WeatherSettings, name: &str, value: &str) -> Result<()> {
+    match name {
+        "api_key" => weather_settings.api_key = parse(name, value)?,
+        "query" => weather_settings.query = parse(name, value)?,
+        "metric" => weather_settings.metric = parse(name, value)?,
+        "cool_above" => weather_settings.cool_above = parse(name, value)?,
+        "heat_below" => weather_settings.heat_below = parse(name, value)?,
+        "off_above" => weather_settings.off_above = parse(name, value)?,
+        "off_below" => weather_settings.off_below = parse(name, value)?,
+        "interval" => weather_settings.interval = parse(name, value)?,
+        "occupied_only" => weather_settings.occupied_only = parse(name, value)?,
+        _ => return Err(Error::Config(format!("Unknown weather setting \"{name}\" (expected one of: {}).", SETTING_NAMES.join(", "))))
+    }
+    Ok(())
+}
+
+/// # parse<T: FromStr>(name: &str, value: &str) -> Result<Option<T>>
+/// 
+/// Parse a setting value, or None for an empty value.
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<Option<T>> {
+    if value.is_empty() {
+        return Ok(None);
     }
-    storage::write_weather_settings(models::WeatherSettings{
-        api_key,
-        query,
-        metric,
-        heat_below,
-        cool_above,
-        off_above,
-        off_below,
-        interval,
-    });    
+    value.parse::<T>().map(Some).map_err(|_| Error::Config(format!("Bad value for {name}: \"{value}\".")))
+}
+
+/// # save(location: &Location, weather_settings: WeatherSettings) -> Result<()>
+/// 
+/// Validate the settings, then write them (creating the configuration directory if needed).
+fn save(location: &Location, weather_settings: WeatherSettings) -> Result<()> {
+    validate(&weather_settings)?;
+    storage::create_config_dir(location)?;
+    storage::write_weather_settings(location, weather_settings)
 }
 
 /// # get_value()
 /// 
 /// Return a string or a parsed int, float, or boolean from stdin using the provided message.
-fn get_value<T: FromStr + ToString>(msg: &str, current_value: Option<T>) -> Option<T> {
-    let mut entry = String::new();
-    let cv = if current_value.is_some() {
-        current_value.as_ref().unwrap().to_string()
-    } else { "unset".to_string() };
-    print!("{msg} (current_value: {})> ", cv);
-    io::stdout().flush().unwrap();
-    io::stdin().read_line(&mut entry).unwrap();
-    entry.truncate(entry.len() - 1);
+/// An entry that doesn't parse is an error, as is running out of input.
+fn get_value<T: FromStr + ToString>(msg: &str, current_value: Option<T>) -> Result<Option<T>> {
+    let cv = match current_value.as_ref() {
+        Some(v) => v.to_string(),
+        None => "unset".to_string()
+    };
+    let entry = prompt::read_line(&format!("{msg} (current_value: {})> ", cv))?;
     if entry == " " {
-        return None;
+        return Ok(None);
     }
     if !entry.is_empty() {
-        let parsed: Result<T, <T as FromStr>::Err> = entry.parse::<T>();
-        return match parsed {
-            Ok(v) => Some(v),
-            _ => None
-        }
-    }
-    if cv == "unset" {
-        return None;
+        return parse(msg, entry.trim());
     }
-    current_value
+    Ok(current_value)
 }
 
-/// # validate()
+/// # validate(weather_settings: &WeatherSettings) -> Result<()>
 /// 
-/// Check for conflicts and bad values.
+/// Check for conflicts and bad values. Every problem found is returned together as a Config error.
 /// 
 /// api_key, interval, and query must be set.
 /// 
@@ -80,55 +127,45 @@ fn get_value<T: FromStr + ToString>(msg: &str, current_value: Option<T>) -> Opti
 /// Where each, if set, must be greater than (and not equal to) the following setting.
 /// 
 /// This should prevent conflicts, or turning off cooling at high temperatures, or turning off heating at low temperatures.
-fn validate(
-    api_key: &Option<String>, query: &Option<String>,
-    cool_above: &Option<f64>, heat_below: &Option<f64>, off_above: &Option<f64>, off_below: &Option<f64>,
-    interval: &Option<u64>
-) -> bool {
-    let mut valid = true;
-    if api_key.is_none() {
-        error!("API Key unset.");
-        valid = false;
-    }
-    if interval.is_none() {
-        error!("Interval unset.");
-        valid = false;
+pub fn validate(weather_settings: &WeatherSettings) -> Result<()> {
+    let mut problems = vec![];
+    if weather_settings.api_key.as_deref().is_none_or(str::is_empty) {
+        problems.push("API Key unset.".to_string());
     }
-    if query.is_none() {
-        error!("Query unset.");
-        valid = false;
+    if weather_settings.query.as_deref().is_none_or(str::is_empty) {
+        problems.push("Query unset.".to_string());
     }
-    if cool_above.is_none() {
-        error!("Cool Above unset.");
-        valid = false;
+    match weather_settings.interval {
+        None => problems.push("Interval unset.".to_string()),
+        Some(interval) if interval < 1 => problems.push("Setting interval to less than every minute.".to_string()),
+        Some(_) => ()
     }
-    if heat_below.is_none() {
-        error!("Heat Below unset.");
-        valid = false;
-    }
-    if cool_above.unwrap() <= heat_below.unwrap() {
-        error!("Setting Cool Above ({}) to less than or equal to Heat Below ({}).", cool_above.unwrap(), heat_below.unwrap());
-        valid = false;
-    }
-    if interval.unwrap() < 1 {
-        error!("Setting interval to less than every minute.");
-        valid = false;
-    }
-    if off_above.is_some() && off_below.is_some() {
-        if !(cool_above.unwrap() > off_above.unwrap() && off_above.unwrap() > off_below.unwrap() && off_below.unwrap() > heat_below.unwrap()) {
-            error!("Cool Above > Off Above > Off Below > Heat Below is not true.");
-            valid = false;
+    match (weather_settings.cool_above, weather_settings.heat_below) {
+        (Some(cool_above), Some(heat_below)) => {
+            if cool_above <= heat_below {
+                problems.push(format!("Setting Cool Above ({cool_above}) to less than or equal to Heat Below ({heat_below})."));
+            }
+            match (weather_settings.off_above, weather_settings.off_below) {
+                (Some(off_above), Some(off_below)) if !(cool_above > off_above && off_above > off_below && off_below > heat_below) =>
+                    problems.push("Cool Above > Off Above > Off Below > Heat Below is not true.".to_string()),
+                (Some(off_above), None) if !(cool_above > off_above && off_above > heat_below) =>
+                    problems.push("Cool Above > Off Above > Heat Below is not true.".to_string()),
+                (None, Some(off_below)) if !(cool_above > off_below && off_below > heat_below) =>
+                    problems.push("Cool Above > Off Below > Heat Below is not true.".to_string()),
+                _ => ()
+            }
+        },
+        (cool_above, heat_below) => {
+            if cool_above.is_none() {
+                problems.push("Cool Above unset.".to_string());
+            }
+            if heat_below.is_none() {
+                problems.push("Heat Below unset.".to_string());
+            }
         }
     }
-    else if off_above.is_some() {
-        if !(cool_above.unwrap() > off_above.unwrap() && off_above.unwrap() > heat_below.unwrap()) {
-            error!("Cool Above > Off Above > Heat Below is not true.");
-            valid = false;
-        }
-    }
-    else if off_below.is_some() && !(cool_above.unwrap() > off_below.unwrap() && off_below.unwrap() > heat_below.unwrap()) {
-        error!("Cool Above > Off Below > Heat Below is not true.");
-        valid = false;
+    if problems.is_empty() {
+        return Ok(());
     }
-    valid
-}
\ No newline at end of file
+    Err(Error::Config(format!("Invalid weather settings: {}", problems.join(" "))))
+}
@@ -0,0 +1,336 @@
+//! In-process mock of the Ecobee and WeatherAPI endpoints used by the integration tests.
+//!
+//! Responses are scripted per method and path. Each route holds a queue of responses:
+//! every request pops the next one, and the last one keeps being served once the queue is down to it.
+//! All requests are recorded so tests can assert on what the client sent.
+#![allow(dead_code)]
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use bulk_ecobee_thermostat_control::ecobee::models::Tokens;
+use bulk_ecobee_thermostat_control::http::RetryPolicy;
+use bulk_ecobee_thermostat_control::config::Config;
+use bulk_ecobee_thermostat_control::storage::{self, Location};
+use bulk_ecobee_thermostat_control::{EcobeeClient, Result, TokenStore};
+
+/// # MockResponse
+///
+/// A scripted response: status code, extra headers, and body (always served as JSON).
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl MockResponse {
+    pub fn json(status: u16, body: serde_json::Value) -> Self {
+        MockResponse { status, headers: vec![], body: body.to_string() }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// # RecordedRequest
+///
+/// A request received by the mock server. Query values are percent-decoded, header names lowercased.
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn json_body(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is JSON")
+    }
+
+    pub fn json_query(&self, name: &str) -> serde_json::Value {
+        serde_json::from_str(&self.query[name]).expect("query parameter is JSON")
+    }
+}
+
+struct Route {
+    method: String,
+    path: String,
+    responses: VecDeque<MockResponse>,
+}
+
+/// # MockServer
+pub struct MockServer {
+    url: String,
+    routes: Arc<Mutex<Vec<Route>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+
+    /// Start listening on a random local port. The server thread lives as long as the test process.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let routes: Arc<Mutex<Vec<Route>>> = Arc::new(Mutex::new(vec![]));
+        let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::new(Mutex::new(vec![]));
+        let (thread_routes, thread_requests) = (routes.clone(), requests.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                handle(stream, &thread_routes, &thread_requests);
+            }
+        });
+        MockServer { url, routes, requests }
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    /// Queue a response for `method` and `path` (path without the query string).
+    pub fn on(&self, method: &str, path: &str, response: MockResponse) -> &Self {
+        let mut routes = self.routes.lock().unwrap();
+        match routes.iter_mut().find(|r| r.method == method && r.path == path) {
+            Some(route) => route.responses.push_back(response),
+            None => routes.push(Route {
+                method: method.to_string(),
+                path: path.to_string(),
+                responses: VecDeque::from([response]),
+            }),
+        }
+        self
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.requests().into_iter().filter(|r| r.method == method && r.path == path).collect()
+    }
+
+    /// An `EcobeeClient` pointed at this server, with in-memory tokens and millisecond retry delays.
+    pub fn client(&self, tokens: Option<Tokens>) -> (EcobeeClient, MemoryTokenStore) {
+        let store = MemoryTokenStore::new(tokens);
+        let client = EcobeeClient::new("test-app-key".to_string(), Box::new(store.clone()))
+            .with_base_url(&self.url())
+            .with_retry_policy(fast_retries(3));
+        (client, store)
+    }
+
+    /// A `Config` pointing both base URLs at this server, with millisecond retry delays.
+    pub fn config(&self) -> Config {
+        Config {
+            ecobee_base_url: Some(self.url()),
+            weather_base_url: Some(self.url()),
+            retries: Some(3),
+            retry_delay_ms: Some(1),
+            ..Default::default()
+        }
+    }
+}
+
+fn handle(stream: TcpStream, routes: &Mutex<Vec<Route>>, requests: &Mutex<Vec<RecordedRequest>>) {
+    let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+        return;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+        }
+    }
+    let length: usize = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).expect("read request body");
+
+    let (path, query_string) = target.split_once('?').unwrap_or((target.as_str(), ""));
+    let query = query_string.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(k), percent_decode(v))
+        })
+        .collect();
+
+    requests.lock().unwrap().push(RecordedRequest {
+        method: method.clone(),
+        path: path.to_string(),
+        query,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+    });
+
+    let response = {
+        let mut routes = routes.lock().unwrap();
+        match routes.iter_mut().find(|r| r.method == method && r.path == path) {
+            Some(route) if route.responses.len() > 1 => route.responses.pop_front().unwrap(),
+            Some(route) => route.responses.front().cloned().unwrap(),
+            None => MockResponse::json(404, serde_json::json!({"error": format!("no mock for {method} {path}")})),
+        }
+    };
+
+    let mut out = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+        response.status,
+        if response.status < 400 { "OK" } else { "Error" },
+        response.body.len()
+    );
+    for (name, value) in &response.headers {
+        out.push_str(&format!("{name}: {value}\r\n"));
+    }
+    out.push_str("\r\n");
+    out.push_str(&response.body);
+    let mut stream = stream;
+    let _ = stream.write_all(out.as_bytes());
+    let _ = stream.flush();
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => { out.push(b); i += 2; }
+                    Err(_) => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+/// # MemoryTokenStore
+///
+/// `TokenStore` that keeps tokens in memory, shared between clones so tests can inspect what was saved.
+#[derive(Clone, Default)]
+pub struct MemoryTokenStore {
+    tokens: Arc<Mutex<Option<Tokens>>>,
+}
+
+impl MemoryTokenStore {
+    pub fn new(tokens: Option<Tokens>) -> Self {
+        MemoryTokenStore { tokens: Arc::new(Mutex::new(tokens)) }
+    }
+
+    pub fn saved(&self) -> Option<Tokens> {
+        self.tokens.lock().unwrap().clone()
+    }
+}
+
+impl TokenStore for MemoryTokenStore {
+    fn load(&self) -> Result<Tokens> {
+        self.saved().ok_or_else(|| bulk_ecobee_thermostat_control::Error::Token("No tokens stored".to_string()))
+    }
+
+    fn save(&self, tokens: &Tokens) -> Result<()> {
+        *self.tokens.lock().unwrap() = Some(tokens.clone());
+        Ok(())
+    }
+}
+
+pub fn fast_retries(retries: u32) -> RetryPolicy {
+    RetryPolicy { retries, base_delay: Duration::from_millis(1), max_delay: Duration::from_millis(20) }
+}
+
+/// Tokens that are valid for another hour, so the client won't refresh them unprompted.
+pub fn tokens(access_token: &str, refresh_token: &str) -> Tokens {
+    let now = chrono::Utc::now();
+    Tokens {
+        access_token: access_token.to_string(),
+        refresh_token: refresh_token.to_string(),
+        issued_at: Some(now),
+        expires_at: Some(now + chrono::Duration::hours(1)),
+    }
+}
+
+/// Tokens that expire in `seconds` (negative for already expired).
+pub fn expiring_tokens(access_token: &str, refresh_token: &str, seconds: i64) -> Tokens {
+    Tokens { expires_at: Some(chrono::Utc::now() + chrono::Duration::seconds(seconds)), ..tokens(access_token, refresh_token) }
+}
+
+static HOME: Mutex<()> = Mutex::new(());
+
+/// A scratch HOME directory named `name`, emptied, with no config directory in the environment.
+/// The guard keeps other tests in the same binary from changing these concurrently.
+pub fn scratch_home_only(name: &str) -> (MutexGuard<'static, ()>, PathBuf) {
+    let guard = HOME.lock().unwrap_or_else(|e| e.into_inner());
+    let home = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}_home"));
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    std::env::set_var("HOME", &home);
+    std::env::remove_var(storage::CONFIG_DIR_ENV);
+    std::env::remove_var("XDG_CONFIG_HOME");
+    std::env::remove_var("XDG_STATE_HOME");
+    (guard, home)
+}
+
+/// Like `scratch_home_only`, but with a single config directory in it (as with --config-dir), created and returned
+/// along with the default profile's location in it.
+pub fn scratch_home(name: &str) -> (MutexGuard<'static, ()>, PathBuf, Location) {
+    let (guard, home) = scratch_home_only(name);
+    let dir = home.join("config");
+    let location = Location::new(Some(dir.to_string_lossy().to_string()));
+    storage::create_config_dir(&location).unwrap();
+    (guard, dir, location)
+}
+
+/// A `/1/thermostat` status body for the given (identifier, name, hvacMode) triples.
+pub fn status_body(thermostats: &[(&str, &str, &str)]) -> serde_json::Value {
+    let list: Vec<serde_json::Value> = thermostats.iter().map(|(identifier, name, mode)| serde_json::json!({
+        "identifier": identifier,
+        "name": name,
+        "settings": { "hvacMode": mode },
+        "runtime": {
+            "actualTemperature": 712,
+            "actualHumidity": 41,
+            "desiredHeat": 680,
+            "desiredCool": 760
+        }
+    })).collect();
+    serde_json::json!({
+        "page": { "page": 1, "totalPages": 1, "pageSize": list.len(), "total": list.len() },
+        "thermostatList": list,
+        "status": { "code": 0, "message": "" }
+    })
+}
+
+/// A `/1/thermostatSummary` body for the given (identifier, name, thermostat revision) triples, with no equipment running.
+pub fn summary_body(thermostats: &[(&str, &str, &str)]) -> serde_json::Value {
+    serde_json::json!({
+        "thermostatCount": thermostats.len(),
+        "revisionList": thermostats.iter().map(|(identifier, name, revision)| format!("{identifier}:{name}:true:{revision}:alerts:runtime:interval")).collect::<Vec<_>>(),
+        "statusList": thermostats.iter().map(|(identifier, _, _)| format!("{identifier}:")).collect::<Vec<_>>(),
+        "status": { "code": 0, "message": "" }
+    })
+}
+
+pub fn ok_status() -> serde_json::Value {
+    serde_json::json!({ "status": { "code": 0, "message": "" } })
+}
@@ -0,0 +1,57 @@
+use std::env;
+use std::time::Duration;
+
+use bulk_ecobee_thermostat_control::config::{Config, ECOBEE_URL_ENV, WEATHER_URL_ENV};
+use bulk_ecobee_thermostat_control::units::TemperatureUnit;
+
+// Environment variables are process wide, so every case lives in one test.
+#[test]
+fn base_urls_prefer_env_then_config_then_default() {
+    env::remove_var(ECOBEE_URL_ENV);
+    env::remove_var(WEATHER_URL_ENV);
+
+    let defaults = Config::default();
+    assert_eq!(defaults.ecobee_base_url(), "https://api.ecobee.com");
+    assert_eq!(defaults.weather_base_url(), "https://api.weatherapi.com");
+
+    let configured = Config {
+        ecobee_base_url: Some("http://localhost:9000/".to_string()),
+        weather_base_url: Some("http://localhost:9001".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(configured.ecobee_base_url(), "http://localhost:9000");
+    assert_eq!(configured.weather_base_url(), "http://localhost:9001");
+
+    env::set_var(ECOBEE_URL_ENV, "http://127.0.0.1:7000");
+    env::set_var(WEATHER_URL_ENV, "http://127.0.0.1:7001");
+    assert_eq!(configured.ecobee_base_url(), "http://127.0.0.1:7000");
+    assert_eq!(configured.weather_base_url(), "http://127.0.0.1:7001");
+}
+
+#[test]
+fn retry_policy_uses_configured_values() {
+    let defaults = Config::default().retry_policy();
+    assert_eq!(defaults.retries, 3);
+    assert_eq!(defaults.base_delay, Duration::from_secs(1));
+    assert!(defaults.max_delay >= Duration::from_secs(60));
+
+    let configured = Config { retries: Some(0), retry_delay_ms: Some(250), ..Default::default() }.retry_policy();
+    assert_eq!(configured.retries, 0);
+    assert_eq!(configured.base_delay, Duration::from_millis(250));
+    assert_eq!(configured.max_delay, defaults.max_delay);
+
+    let capped = Config { max_retry_delay_ms: Some(5000), ..Default::default() }.retry_policy();
+    assert_eq!(capped.max_delay, Duration::from_secs(5));
+}
+
+#[test]
+fn units_setting_overrides_weather_metric() {
+    assert_eq!(Config::default().temperature_unit(None), TemperatureUnit::Fahrenheit);
+    assert_eq!(Config::default().temperature_unit(Some(true)), TemperatureUnit::Celsius);
+
+    let fahrenheit = Config { units: Some(TemperatureUnit::Fahrenheit), ..Default::default() };
+    assert_eq!(fahrenheit.temperature_unit(Some(true)), TemperatureUnit::Fahrenheit);
+
+    let celsius: Config = serde_yaml::from_str("units: celsius").unwrap();
+    assert_eq!(celsius.temperature_unit(Some(false)), TemperatureUnit::Celsius);
+}
@@ -0,0 +1,87 @@
+mod common;
+
+use std::fs;
+
+use bulk_ecobee_thermostat_control::storage::{self, Location};
+
+#[test]
+fn settings_and_tokens_follow_xdg_directories() {
+    let (_guard, home) = common::scratch_home_only("directories");
+    std::env::set_var("XDG_CONFIG_HOME", home.join("xdg-config"));
+    std::env::set_var("XDG_STATE_HOME", home.join("xdg-state"));
+    let location = Location::new(None);
+    storage::create_config_dir(&location).unwrap();
+
+    storage::write_api_key(&location, "app-key".to_string()).unwrap();
+    storage::write_tokens(&location, &common::tokens("access-1", "refresh-1")).unwrap();
+
+    assert!(home.join("xdg-config/bulk_ecobee_thermostat_control/api_key").exists());
+    assert!(home.join("xdg-state/bulk_ecobee_thermostat_control/api_tokens").exists());
+}
+
+#[test]
+fn defaults_are_under_home() {
+    let (_guard, home) = common::scratch_home_only("directories");
+    let location = Location::new(None);
+    storage::create_config_dir(&location).unwrap();
+
+    storage::write_api_key(&location, "app-key".to_string()).unwrap();
+    storage::write_tokens(&location, &common::tokens("access-1", "refresh-1")).unwrap();
+
+    assert!(home.join(".config/bulk_ecobee_thermostat_control/api_key").exists());
+    assert!(home.join(".local/state/bulk_ecobee_thermostat_control/api_tokens").exists());
+}
+
+#[test]
+fn config_dir_override_holds_everything() {
+    let (_guard, home) = common::scratch_home_only("directories");
+    std::env::set_var(storage::CONFIG_DIR_ENV, home.join("from-env"));
+    let location = Location::new(None);
+    storage::create_config_dir(&location).unwrap();
+
+    storage::write_api_key(&location, "app-key".to_string()).unwrap();
+    storage::write_tokens(&location, &common::tokens("access-1", "refresh-1")).unwrap();
+
+    assert!(home.join("from-env/api_key").exists());
+    assert!(home.join("from-env/api_tokens").exists());
+
+    let flag = Location::new(Some(home.join("from-flag").to_string_lossy().to_string()));
+    storage::create_config_dir(&flag).unwrap();
+    storage::write_api_key(&flag, "other-key".to_string()).unwrap();
+    assert!(home.join("from-flag/api_key").exists());
+    assert_eq!(storage::load_app_key(&location).unwrap(), "app-key");
+}
+
+#[test]
+fn legacy_directory_is_migrated_once() {
+    let (_guard, home) = common::scratch_home_only("directories");
+    let legacy = home.join(".bulk_ecobee_thermostat_control");
+    fs::create_dir_all(legacy.join("profiles/office")).unwrap();
+    fs::write(legacy.join("api_key"), "app-key").unwrap();
+    fs::write(legacy.join("api_tokens"), "access-1\nrefresh-1").unwrap();
+    fs::write(legacy.join("api_tokens.lock"), "").unwrap();
+    fs::write(legacy.join("groups.yaml"), "main: [Kitchen]\n").unwrap();
+    fs::write(legacy.join("profiles/office/api_key"), "office-key").unwrap();
+    fs::write(legacy.join("profiles/office/api_tokens"), "office-access\noffice-refresh").unwrap();
+    let location = Location::new(None);
+
+    storage::migrate_legacy_dir(&location).unwrap();
+
+    let config = home.join(".config/bulk_ecobee_thermostat_control");
+    let state = home.join(".local/state/bulk_ecobee_thermostat_control");
+    assert!(!legacy.exists());
+    assert_eq!(fs::read_to_string(config.join("api_key")).unwrap(), "app-key");
+    assert!(config.join("groups.yaml").exists());
+    assert!(state.join("api_tokens").exists());
+    assert!(!state.join("api_tokens.lock").exists());
+    assert!(config.join("profiles/office/api_key").exists());
+    assert!(state.join("profiles/office/api_tokens").exists());
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-1");
+
+    // Once the new directories exist, a legacy directory is left alone.
+    fs::create_dir_all(&legacy).unwrap();
+    fs::write(legacy.join("api_key"), "stale-key").unwrap();
+    storage::migrate_legacy_dir(&location).unwrap();
+    assert_eq!(storage::load_app_key(&location).unwrap(), "app-key");
+    assert!(legacy.join("api_key").exists());
+}
@@ -0,0 +1,506 @@
+mod common;
+
+use common::{ok_status, status_body, tokens, MockResponse, MockServer};
+use serde_json::json;
+
+use bulk_ecobee_thermostat_control::ecobee::models::{FanMode, Hold, HoldType, HvacMode, HvacModeStatus, ThermostatMeta, ThermostatMode, ThermostatRevision, Vacation};
+use bulk_ecobee_thermostat_control::units::TemperatureUnit;
+use bulk_ecobee_thermostat_control::Error;
+
+fn token_response(access: &str, refresh: &str) -> MockResponse {
+    MockResponse::json(200, json!({
+        "access_token": access,
+        "token_type": "Bearer",
+        "expires_in": 3599,
+        "refresh_token": refresh,
+        "scope": "smartWrite"
+    }))
+}
+
+fn metas(ids: &[(&str, &str)]) -> Vec<ThermostatMeta> {
+    ids.iter().map(|(identifier, name)| ThermostatMeta { identifier: identifier.to_string(), name: name.to_string() }).collect()
+}
+
+#[test]
+fn pin_flow_stores_code_then_tokens() {
+    let server = MockServer::start();
+    server.on("GET", "/authorize", MockResponse::json(200, json!({
+        "ecobeePin": "ABCD", "expires_in": 9, "code": "auth-code", "scope": "smartWrite", "interval": 30
+    })));
+    server.on("POST", "/token", token_response("access-1", "refresh-1"));
+    let (mut client, store) = server.client(None);
+
+    let auth = client.authorize().unwrap();
+    assert_eq!(auth.ecobee_pin, "ABCD");
+    assert_eq!(store.saved().unwrap().access_token, "auth-code");
+    let authorize = &server.requests_to("GET", "/authorize")[0];
+    assert_eq!(authorize.query["client_id"], "test-app-key");
+    assert_eq!(authorize.query["response_type"], "ecobeePin");
+
+    client.get_tokens_with_code().unwrap();
+    let token = &server.requests_to("POST", "/token")[0];
+    assert_eq!(token.query["grant_type"], "ecobeePin");
+    assert_eq!(token.query["code"], "auth-code");
+    let saved = store.saved().unwrap();
+    assert_eq!(saved.access_token, "access-1");
+    assert_eq!(saved.refresh_token, "refresh-1");
+}
+
+fn authorize_response(expires_in: u32) -> MockResponse {
+    MockResponse::json(200, json!({
+        "ecobeePin": "ABCD", "expires_in": expires_in, "code": "auth-code", "scope": "smartWrite", "interval": 0
+    }))
+}
+
+fn oauth_error(error: &str) -> MockResponse {
+    MockResponse::json(401, json!({"error": error, "error_description": "", "error_uri": ""}))
+}
+
+#[test]
+fn setup_polls_until_the_pin_is_registered() {
+    let server = MockServer::start();
+    server.on("GET", "/authorize", authorize_response(9));
+    server.on("POST", "/token", oauth_error("authorization_pending"));
+    server.on("POST", "/token", oauth_error("slow_down"));
+    server.on("POST", "/token", oauth_error("authorization_pending"));
+    server.on("POST", "/token", token_response("access-1", "refresh-1"));
+    let (mut client, store) = server.client(None);
+
+    let auth = client.authorize().unwrap();
+    client.wait_for_authorization(&auth).unwrap();
+
+    let polls = server.requests_to("POST", "/token");
+    assert_eq!(polls.len(), 4);
+    assert!(polls.iter().all(|p| p.query["code"] == "auth-code" && p.query["grant_type"] == "ecobeePin"));
+    assert_eq!(store.saved().unwrap().refresh_token, "refresh-1");
+}
+
+#[test]
+fn setup_gives_up_when_the_pin_expires() {
+    let server = MockServer::start();
+    server.on("GET", "/authorize", authorize_response(0));
+    let (mut client, _) = server.client(None);
+
+    let auth = client.authorize().unwrap();
+
+    assert!(matches!(client.wait_for_authorization(&auth), Err(Error::Token(_))));
+    assert!(server.requests_to("POST", "/token").is_empty());
+}
+
+#[test]
+fn setup_stops_on_other_token_errors() {
+    let server = MockServer::start();
+    server.on("GET", "/authorize", authorize_response(9));
+    server.on("POST", "/token", oauth_error("access_denied"));
+    let (mut client, _) = server.client(None);
+
+    let auth = client.authorize().unwrap();
+    let error = client.wait_for_authorization(&auth).unwrap_err();
+
+    assert_eq!(error.oauth_error().as_deref(), Some("access_denied"));
+    assert_eq!(server.requests_to("POST", "/token").len(), 1);
+}
+
+#[test]
+fn refresh_sends_and_rotates_refresh_token() {
+    let server = MockServer::start();
+    server.on("POST", "/token", token_response("access-2", "refresh-2"));
+    let (mut client, store) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    client.refresh_tokens().unwrap();
+
+    let token = &server.requests_to("POST", "/token")[0];
+    assert_eq!(token.query["grant_type"], "refresh_token");
+    assert_eq!(token.query["refresh_token"], "refresh-1");
+    assert_eq!(store.saved().unwrap().refresh_token, "refresh-2");
+    assert_eq!(client.tokens().unwrap().access_token, "access-2");
+}
+
+#[test]
+fn refresh_without_refresh_token_is_a_token_error() {
+    let server = MockServer::start();
+    let (mut client, _) = server.client(Some(tokens("auth-code", "")));
+
+    assert!(matches!(client.refresh_tokens(), Err(Error::Token(_))));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn status_parses_thermostats() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[
+        ("111", "Upstairs", "heat"),
+        ("222", "Downstairs", "heat"),
+    ])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let status = client.thermostat_status().unwrap();
+
+    assert_eq!(status.thermostats.len(), 2);
+    assert_eq!(status.thermostats[1].name, "Downstairs");
+    assert_eq!(status.thermostats[0].runtime.actual_temperature, 712.0);
+    assert_eq!(status.hvac_mode, HvacModeStatus::Uniform(HvacMode::Heat));
+    let request = &server.requests_to("GET", "/1/thermostat")[0];
+    assert_eq!(request.headers["authorization"], "Bearer access-1");
+    assert_eq!(request.json_query("json")["selection"]["selectionType"], "registered");
+    assert_eq!(request.json_query("json")["selection"]["includeEquipmentStatus"], "true");
+    assert_eq!(request.json_query("json")["selection"]["includeSensors"], "true");
+}
+
+#[test]
+fn status_reports_mixed_modes() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[
+        ("111", "Upstairs", "heat"),
+        ("222", "Downstairs", "auxHeatOnly"),
+    ])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    match client.thermostat_status().unwrap().hvac_mode {
+        HvacModeStatus::Mixed(modes) => {
+            assert_eq!(modes["111"], ThermostatMode { name: "Upstairs".to_string(), mode: HvacMode::Heat });
+            assert_eq!(modes["222"], ThermostatMode { name: "Downstairs".to_string(), mode: HvacMode::AuxHeatOnly });
+        }
+        other => panic!("expected mixed modes, got {other:?}"),
+    }
+}
+
+#[test]
+fn thermostats_sharing_a_name_are_not_merged() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[
+        ("111", "Hallway", "heat"),
+        ("222", "Hallway", "off"),
+    ])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let hvac_mode = client.thermostat_status().unwrap().hvac_mode;
+
+    assert_eq!(hvac_mode.uniform(), None);
+    assert_eq!(hvac_mode.to_string(), "inconsistent (Hallway: heat, Hallway: off)");
+}
+
+fn page(body: serde_json::Value, page: u32, total_pages: u32) -> MockResponse {
+    let mut body = body;
+    body["page"] = json!({ "page": page, "totalPages": total_pages, "pageSize": 2, "total": 3 });
+    MockResponse::json(200, body)
+}
+
+#[test]
+fn status_follows_every_page() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", page(status_body(&[("111", "Upstairs", "heat"), ("222", "Downstairs", "heat")]), 1, 2))
+        .on("GET", "/1/thermostat", page(status_body(&[("333", "Basement", "cool")]), 2, 2));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let status = client.thermostat_status().unwrap();
+
+    let identifiers: Vec<&str> = status.thermostats.iter().map(|t| t.identifier.as_str()).collect();
+    assert_eq!(identifiers, ["111", "222", "333"]);
+    assert!(matches!(status.hvac_mode, HvacModeStatus::Mixed(_)));
+    let requests = server.requests_to("GET", "/1/thermostat");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].json_query("json")["page"]["page"], 1);
+    assert_eq!(requests[1].json_query("json")["page"]["page"], 2);
+    assert_eq!(requests[1].json_query("json")["selection"]["selectionType"], "registered");
+}
+
+#[test]
+fn status_without_page_is_a_single_page() {
+    let server = MockServer::start();
+    let mut body = status_body(&[("111", "Upstairs", "heat")]);
+    body.as_object_mut().unwrap().remove("page");
+    server.on("GET", "/1/thermostat", MockResponse::json(200, body));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    assert_eq!(client.thermostat_status().unwrap().thermostats.len(), 1);
+    assert_eq!(server.requests_to("GET", "/1/thermostat").len(), 1);
+}
+
+#[test]
+fn status_error_is_surfaced() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(500, json!({"status": {"code": 3, "message": "Processing error."}})));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    match client.thermostat_status() {
+        Err(Error::Status { code, body }) => {
+            assert_eq!(code, 500);
+            assert!(body.contains("Processing error."));
+        }
+        other => panic!("expected a status error, got {other:?}"),
+    }
+}
+
+#[test]
+fn summary_parses_revisions_and_equipment() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, json!({
+        "thermostatCount": 2,
+        "revisionList": ["111:Upstairs:true:170101:170102:170103:170104", "222:Den: Back:false:2:3:4:5"],
+        "statusList": ["111:heatPump,fan", "222:"],
+        "status": { "code": 0, "message": "" }
+    })));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let summary = client.thermostat_summary().unwrap();
+
+    assert_eq!(summary.revisions[0], ThermostatRevision {
+        identifier: "111".to_string(),
+        name: "Upstairs".to_string(),
+        connected: true,
+        thermostat_revision: "170101".to_string(),
+        alerts_revision: "170102".to_string(),
+        runtime_revision: "170103".to_string(),
+        interval_revision: "170104".to_string(),
+    });
+    assert_eq!(summary.revisions[1].name, "Den: Back");
+    assert!(!summary.revisions[1].connected);
+    assert_eq!(summary.equipment[0].running, ["heatPump", "fan"]);
+    assert!(summary.equipment[1].running.is_empty());
+    let request = &server.requests_to("GET", "/1/thermostatSummary")[0];
+    assert_eq!(request.json_query("json")["selection"]["includeEquipmentStatus"], true);
+}
+
+#[test]
+fn summary_rejects_malformed_revisions() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, json!({"revisionList": ["111:Upstairs:true"], "statusList": []})));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    assert!(matches!(client.thermostat_summary(), Err(Error::Json(_))));
+}
+
+#[test]
+fn summary_changes_ignore_runtime_revisions() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, json!({"revisionList": [
+        "111:Upstairs:true:1:1:1:1", "222:Downstairs:true:1:1:1:1"
+    ]})));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, json!({"revisionList": [
+        "111:Upstairs:true:1:1:2:2", "222:Downstairs:true:2:1:1:1", "333:Basement:true:1:1:1:1"
+    ]})));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let previous = client.thermostat_summary().unwrap();
+    let latest = client.thermostat_summary().unwrap();
+
+    assert_eq!(latest.changed_since(&previous), ["222", "333"]);
+    assert!(previous.changed_since(&previous).is_empty());
+}
+
+#[test]
+fn alerts_are_requested_and_listed_across_thermostats() {
+    let server = MockServer::start();
+    let mut body = status_body(&[("111", "Upstairs", "heat"), ("222", "Downstairs", "heat")]);
+    body["thermostatList"][1]["alerts"] = json!([
+        {"acknowledgeRef": "222$1", "date": "2026-10-16", "time": "07:30:00", "severity": "medium",
+         "text": "Your furnace filter needs replacing.", "alertNumber": 610, "notificationType": "furnaceFilter"},
+        {"acknowledgeRef": "222$2", "date": "2026-10-17", "time": "03:10:00", "severity": "high",
+         "text": "Low temperature alert.", "alertNumber": 500, "notificationType": "lowTemp"}
+    ]);
+    server.on("GET", "/1/thermostat", MockResponse::json(200, body));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let alerts = client.alerts().unwrap();
+
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(alerts[0].thermostat.name, "Downstairs");
+    assert_eq!(alerts[0].alert.acknowledge_ref, "222$1");
+    assert_eq!(alerts[1].alert.notification_type, "lowTemp");
+    let selection = &server.requests_to("GET", "/1/thermostat")[0].json_query("json")["selection"];
+    assert_eq!(selection["includeAlerts"], "true");
+    assert!(selection.get("includeAlertsv").is_none());
+}
+
+#[test]
+fn acknowledge_alert_sends_acknowledge_function() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let report = client.acknowledge_alert(&metas(&[("222", "Downstairs")])[0], "222$1").unwrap();
+
+    assert!(report.into_result().is_ok());
+    let body = server.requests_to("POST", "/1/thermostat")[0].json_body();
+    assert_eq!(body["selection"]["selectionMatch"], "222");
+    assert_eq!(body["functions"][0], json!({
+        "type": "acknowledge",
+        "params": {"thermostatIdentifier": "222", "ackRef": "222$1", "ackType": "accept", "remindMeLater": false}
+    }));
+}
+
+#[test]
+fn update_makes_one_call_per_thermostat() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    client.update_thermostats(&metas(&[("111", "Upstairs"), ("222", "Downstairs")]), HvacMode::Cool).unwrap().into_result().unwrap();
+
+    let updates = server.requests_to("POST", "/1/thermostat");
+    assert_eq!(updates.len(), 2);
+    for (update, id) in updates.iter().zip(["111", "222"]) {
+        let body = update.json_body();
+        assert_eq!(body["selection"]["selectionType"], "thermostats");
+        assert_eq!(body["selection"]["selectionMatch"], id);
+        assert_eq!(body["thermostat"]["settings"]["hvacMode"], "cool");
+    }
+}
+
+#[test]
+fn update_continues_after_a_failure() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(400, json!({"status": {"code": 4, "message": "Serialization error."}})));
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let report = client.update_thermostats(&metas(&[("111", "Upstairs"), ("222", "Downstairs")]), HvacMode::Off).unwrap();
+
+    assert_eq!(server.requests_to("POST", "/1/thermostat").len(), 2);
+    assert!(matches!(report.results[0].result, Err(Error::Status { code: 400, .. })));
+    assert!(report.results[1].result.is_ok());
+    assert_eq!(report.to_string(), "off: 1 succeeded, 1 failed (Upstairs)");
+    match report.into_result() {
+        Err(Error::Update { failed, total }) => {
+            assert_eq!(failed, ["Upstairs"]);
+            assert_eq!(total, 2);
+        }
+        other => panic!("expected an update error, got {other:?}"),
+    }
+}
+
+#[test]
+fn update_checks_status_code_inside_a_200() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    server.on("POST", "/1/thermostat", MockResponse::json(200, json!({"status": {"code": 2, "message": "Thermostat not found."}})));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let report = client.update_thermostats(&metas(&[("111", "Upstairs"), ("222", "Downstairs")]), HvacMode::Heat).unwrap();
+
+    assert_eq!(report.succeeded().len(), 1);
+    match &report.results[1].result {
+        Err(Error::Api { code, message }) => {
+            assert_eq!(*code, 2);
+            assert_eq!(message, "Thermostat not found.");
+        }
+        other => panic!("expected an api error, got {other:?}"),
+    }
+    assert_eq!(report.into_result().unwrap_err().exit_code(), 9);
+}
+
+#[test]
+fn set_hold_sends_set_hold_function_per_thermostat() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    let hold = Hold { heat_hold_temp: 680, cool_hold_temp: 760, hold_type: HoldType::HoldHours(2) };
+
+    let report = client.set_hold(&metas(&[("111", "Upstairs"), ("222", "Downstairs")]), &hold, TemperatureUnit::Celsius).unwrap();
+
+    assert_eq!(report.description, "hold heat 20°C / cool 24.4°C");
+    report.into_result().unwrap();
+
+    let updates = server.requests_to("POST", "/1/thermostat");
+    assert_eq!(updates.len(), 2);
+    let body = updates[1].json_body();
+    assert_eq!(body["selection"]["selectionMatch"], "222");
+    assert_eq!(body["functions"][0]["type"], "setHold");
+    assert_eq!(body["functions"][0]["params"], json!({
+        "holdType": "holdHours", "holdHours": 2, "heatHoldTemp": 680, "coolHoldTemp": 760
+    }));
+}
+
+#[test]
+fn hold_params_for_date_time() {
+    let end = chrono::NaiveDate::from_ymd_opt(2026, 12, 24).unwrap().and_hms_opt(18, 30, 0).unwrap();
+    let hold = Hold { heat_hold_temp: 700, cool_hold_temp: 780, hold_type: HoldType::DateTime(end) };
+
+    let params = hold.params();
+
+    assert_eq!(params["holdType"], "dateTime");
+    assert_eq!(params["endDate"], "2026-12-24");
+    assert_eq!(params["endTime"], "18:30:00");
+}
+
+#[test]
+fn create_vacation_sends_create_vacation_function_per_thermostat() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    let vacation = Vacation {
+        name: "Holidays".to_string(),
+        start: chrono::NaiveDate::from_ymd_opt(2026, 12, 20).unwrap().and_hms_opt(8, 0, 0).unwrap(),
+        end: chrono::NaiveDate::from_ymd_opt(2026, 12, 27).unwrap().and_hms_opt(18, 30, 0).unwrap(),
+        heat_hold_temp: 550,
+        cool_hold_temp: 850,
+        fan: FanMode::On,
+    };
+
+    client.create_vacation(&metas(&[("111", "Upstairs"), ("222", "Downstairs")]), &vacation).unwrap().into_result().unwrap();
+
+    let updates = server.requests_to("POST", "/1/thermostat");
+    assert_eq!(updates.len(), 2);
+    let body = updates[1].json_body();
+    assert_eq!(body["selection"]["selectionMatch"], "222");
+    assert_eq!(body["functions"][0], json!({
+        "type": "createVacation",
+        "params": {"name": "Holidays", "heatHoldTemp": 550, "coolHoldTemp": 850, "startDate": "2026-12-20", "startTime": "08:00:00",
+                   "endDate": "2026-12-27", "endTime": "18:30:00", "fan": "on"}
+    }));
+}
+
+#[test]
+fn delete_vacation_sends_delete_vacation_function_per_thermostat() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let report = client.delete_vacation(&metas(&[("111", "Upstairs"), ("222", "Downstairs")]), "Holidays").unwrap();
+
+    assert_eq!(report.description, "delete vacation Holidays");
+    let updates = server.requests_to("POST", "/1/thermostat");
+    assert_eq!(updates.len(), 2);
+    for update in updates {
+        assert_eq!(update.json_body()["functions"][0], json!({"type": "deleteVacation", "params": {"name": "Holidays"}}));
+    }
+}
+
+#[test]
+fn vacations_are_listed_from_events() {
+    let server = MockServer::start();
+    let mut body = status_body(&[("111", "Upstairs", "heat"), ("222", "Downstairs", "heat")]);
+    body["thermostatList"][0]["events"] = json!([
+        {"type": "hold", "name": "auto", "running": true, "endDate": "2026-12-24", "endTime": "18:00:00", "heatHoldTemp": 680, "coolHoldTemp": 770},
+        {"type": "vacation", "name": "Holidays", "running": false, "startDate": "2026-12-20", "startTime": "08:00:00",
+         "endDate": "2026-12-27", "endTime": "18:30:00", "heatHoldTemp": 550, "coolHoldTemp": 850, "fan": "auto"}
+    ]);
+    server.on("GET", "/1/thermostat", MockResponse::json(200, body));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let vacations = client.vacations().unwrap();
+
+    assert_eq!(vacations.len(), 1);
+    assert_eq!(vacations[0].thermostat.name, "Upstairs");
+    assert_eq!(vacations[0].vacation.name, "Holidays");
+    assert_eq!(vacations[0].vacation.start_date, "2026-12-20");
+    assert_eq!(vacations[0].vacation.fan, "auto");
+    let selection = &server.requests_to("GET", "/1/thermostat")[0].json_query("json")["selection"];
+    assert_eq!(selection["includeEvents"], "true");
+}
+
+#[test]
+fn resume_program_clears_all_holds_per_thermostat() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    client.resume_program(&metas(&[("111", "Upstairs"), ("222", "Downstairs")]), true).unwrap().into_result().unwrap();
+
+    let updates = server.requests_to("POST", "/1/thermostat");
+    assert_eq!(updates.len(), 2);
+    let body = updates[0].json_body();
+    assert_eq!(body["selection"]["selectionMatch"], "111");
+    assert_eq!(body["functions"][0], json!({"type": "resumeProgram", "params": {"resumeAll": true}}));
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::status_body;
+use serde_json::json;
+
+use bulk_ecobee_thermostat_control::ecobee::models::{StatusResponse, ThermostatMeta, ThermostatVacation, UpdateReport, UpdateResult};
+use bulk_ecobee_thermostat_control::output::{OutputFormat, StatusDocument, Table, UpdateDocument, VacationsDocument};
+use bulk_ecobee_thermostat_control::units::TemperatureUnit;
+use bulk_ecobee_thermostat_control::Error;
+
+fn status() -> StatusResponse {
+    serde_json::from_value(status_body(&[("111", "Upstairs", "heat"), ("222", "Downstairs", "auxHeatOnly")])).unwrap()
+}
+
+fn report() -> UpdateReport {
+    let meta = |identifier: &str, name: &str| ThermostatMeta { identifier: identifier.to_string(), name: name.to_string() };
+    UpdateReport {
+        description: "cool".to_string(),
+        results: vec![
+            UpdateResult { thermostat: meta("111", "Upstairs"), result: Ok(()) },
+            UpdateResult { thermostat: meta("222", "Downstairs"), result: Err(Error::Api { code: 3, message: "Processing error.".to_string() }) },
+        ],
+    }
+}
+
+#[test]
+fn status_document_has_a_record_per_thermostat() {
+    let document = StatusDocument::new(None, &status().thermostats, TemperatureUnit::Fahrenheit);
+
+    let value: serde_json::Value = serde_json::from_str(&OutputFormat::Json.render(&document).unwrap()).unwrap();
+
+    assert_eq!(value, json!({"thermostats": [
+        {"identifier": "111", "name": "Upstairs", "hvacMode": "heat", "running": [], "actualTemperature": 71.2, "actualHumidity": 41.0,
+         "desiredHeat": 68.0, "desiredCool": 76.0, "hold": null, "sensors": [], "alerts": [], "units": "fahrenheit"},
+        {"identifier": "222", "name": "Downstairs", "hvacMode": "auxHeatOnly", "running": [], "actualTemperature": 71.2, "actualHumidity": 41.0,
+         "desiredHeat": 68.0, "desiredCool": 76.0, "hold": null, "sensors": [], "alerts": [], "units": "fahrenheit"}
+    ]}));
+}
+
+#[test]
+fn update_document_reports_each_thermostat() {
+    let document = UpdateDocument::new(Some("cabin".to_string()), &[report()], vec!["Upstairs".to_string()]);
+
+    let yaml = OutputFormat::Yaml.render(&document).unwrap();
+    let value: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+
+    assert!(yaml.starts_with("---\n"));
+    assert_eq!(value, json!({
+        "profile": "cabin",
+        "actions": [{"action": "cool", "results": [
+            {"identifier": "111", "name": "Upstairs", "succeeded": true},
+            {"identifier": "222", "name": "Downstairs", "succeeded": false, "error": "Ecobee status 3: Processing error."}
+        ]}],
+        "unconverged": ["Upstairs"]
+    }));
+}
+
+#[test]
+fn table_aligns_columns() {
+    let mut table = Table::new(&["Name", "Mode"]);
+    table.push(vec!["Upstairs".to_string(), "heat".to_string()]);
+    table.push(vec!["Den".to_string(), "auxHeatOnly".to_string()]);
+
+    assert_eq!(table.to_string(), "\
+Name      Mode
+--------  -----------
+Upstairs  heat
+Den       auxHeatOnly
+");
+}
+
+#[test]
+fn status_table_converts_units_and_shows_equipment_and_holds() {
+    let mut body = status_body(&[("111", "Upstairs", "heat"), ("222", "Downstairs", "heat")]);
+    body["thermostatList"][0]["equipmentStatus"] = json!("heatPump,fan");
+    body["thermostatList"][1]["events"] = json!([
+        {"type": "vacation", "name": "Away", "running": false, "endDate": "2026-12-31", "endTime": "08:00:00", "heatHoldTemp": 550, "coolHoldTemp": 850},
+        {"type": "hold", "name": "auto", "running": true, "endDate": "2026-12-24", "endTime": "18:00:00", "heatHoldTemp": 680, "coolHoldTemp": 770}
+    ]);
+    let status: StatusResponse = serde_json::from_value(body).unwrap();
+
+    let table = StatusDocument::new(None, &status.thermostats, TemperatureUnit::Celsius).table().to_string();
+
+    assert_eq!(table, "\
+Name        Mode  Running        Temp    Humidity  Heat  Cool    Alerts  Hold
+----------  ----  -------------  ------  --------  ----  ------  ------  -----------------------------------
+Upstairs    heat  heatPump, fan  21.8°C  41%       20°C  24.4°C  0       -
+Downstairs  heat  -              21.8°C  41%       20°C  24.4°C  0       20°C/25°C until 2026-12-24 18:00:00
+");
+}
+
+#[test]
+fn sensor_table_lists_every_sensor_under_its_thermostat() {
+    let mut body = status_body(&[("111", "Upstairs", "heat")]);
+    body["thermostatList"][0]["remoteSensors"] = json!([
+        {"id": "ei:0", "name": "Upstairs", "type": "thermostat", "inUse": true, "capability": [
+            {"id": "1", "type": "temperature", "value": "712"},
+            {"id": "2", "type": "humidity", "value": "41"},
+            {"id": "3", "type": "occupancy", "value": "true"}
+        ]},
+        {"id": "rs:100", "name": "Bedroom", "type": "ecobee3_remote_sensor", "inUse": false, "capability": [
+            {"id": "1", "type": "temperature", "value": "unknown"},
+            {"id": "2", "type": "occupancy", "value": "false"}
+        ]}
+    ]);
+    let status: StatusResponse = serde_json::from_value(body).unwrap();
+
+    let table = StatusDocument::new(None, &status.thermostats, TemperatureUnit::Fahrenheit).sensor_table().to_string();
+
+    assert_eq!(table, "\
+Thermostat  Sensor    Temp    Humidity  Occupied
+----------  --------  ------  --------  --------
+Upstairs    Upstairs  71.2°F  41%       yes
+Upstairs    Bedroom   -       -         no
+");
+}
+
+#[test]
+fn vacations_document_converts_units() {
+    let mut body = status_body(&[("111", "Upstairs", "heat")]);
+    body["thermostatList"][0]["events"] = json!([
+        {"type": "vacation", "name": "Holidays", "running": true, "startDate": "2026-12-20", "startTime": "08:00:00",
+         "endDate": "2026-12-27", "endTime": "18:30:00", "heatHoldTemp": 590, "coolHoldTemp": 860, "fan": "auto"}
+    ]);
+    let status: StatusResponse = serde_json::from_value(body).unwrap();
+    let vacations = status.thermostats[0].vacations().into_iter()
+        .map(|v| ThermostatVacation { thermostat: status.thermostats[0].meta(), vacation: v.clone() })
+        .collect();
+
+    let document = VacationsDocument::new(None, vacations, TemperatureUnit::Celsius);
+    let value: serde_json::Value = serde_json::from_str(&OutputFormat::Json.render(&document).unwrap()).unwrap();
+
+    assert_eq!(value, json!({"vacations": [
+        {"identifier": "111", "name": "Upstairs", "vacation": "Holidays", "start": "2026-12-20 08:00:00", "end": "2026-12-27 18:30:00",
+         "heat": 15.0, "cool": 30.0, "fan": "auto", "running": true, "units": "celsius"}
+    ]}));
+    assert_eq!(document.table().to_string(), "\
+Thermostat  Vacation  Start                End                  Heat  Cool  Fan   Running
+----------  --------  -------------------  -------------------  ----  ----  ----  -------
+Upstairs    Holidays  2026-12-20 08:00:00  2026-12-27 18:30:00  15°C  30°C  auto  yes
+");
+}
+
+#[test]
+fn output_formats_parse_and_only_documents_render() {
+    assert_eq!("json".parse::<OutputFormat>(), Ok(OutputFormat::Json));
+    assert_eq!("table".parse::<OutputFormat>(), Ok(OutputFormat::Table));
+    assert!("xml".parse::<OutputFormat>().is_err());
+    assert_eq!("ndjson".parse::<OutputFormat>(), Ok(OutputFormat::Ndjson));
+    assert!(OutputFormat::Ndjson.is_document());
+    assert_eq!(OutputFormat::Ndjson.render(&json!({"profile": "cabin", "alerts": []})).unwrap(), r#"{"alerts":[],"profile":"cabin"}"#);
+    assert!(!OutputFormat::Text.is_document());
+    assert!(matches!(OutputFormat::Table.render(&json!({})), Err(Error::Config(_))));
+}
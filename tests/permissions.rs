@@ -0,0 +1,43 @@
+#![cfg(unix)]
+mod common;
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use bulk_ecobee_thermostat_control::storage;
+use bulk_ecobee_thermostat_control::weather::models::WeatherSettings;
+
+fn mode(path: &std::path::Path) -> u32 {
+    fs::metadata(path).unwrap().permissions().mode() & 0o777
+}
+
+#[test]
+fn secrets_are_private_to_the_owner() {
+    let (_guard, dir, location) = common::scratch_home("permissions");
+
+    storage::write_api_key(&location, "app-key".to_string()).unwrap();
+    storage::write_tokens(&location, &common::tokens("access-1", "refresh-1")).unwrap();
+    storage::write_weather_settings(&location, WeatherSettings::default()).unwrap();
+
+    assert_eq!(mode(&dir), 0o700);
+    for file in ["api_key", "api_tokens", "weather.yaml"] {
+        assert_eq!(mode(&dir.join(file)), 0o600, "{file}");
+    }
+    assert!(storage::check_permissions(&location).unwrap().is_empty());
+}
+
+#[test]
+fn permissive_files_are_reported() {
+    let (_guard, dir, location) = common::scratch_home("permissions");
+    fs::write(dir.join("api_key"), "app-key").unwrap();
+    fs::set_permissions(dir.join("api_key"), fs::Permissions::from_mode(0o644)).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let problems = storage::check_permissions(&location).unwrap();
+
+    assert_eq!(problems.len(), 2, "{problems:?}");
+    assert!(problems[1].contains("api_key"));
+
+    storage::write_api_key(&location, "app-key".to_string()).unwrap();
+    assert_eq!(mode(&dir.join("api_key")), 0o600);
+}
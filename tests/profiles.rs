@@ -0,0 +1,56 @@
+mod common;
+
+use std::fs;
+
+use bulk_ecobee_thermostat_control::storage;
+
+#[test]
+fn profiles_keep_separate_accounts() {
+    let (_guard, dir, location) = common::scratch_home("profiles");
+    storage::write_api_key(&location, "default-key".to_string()).unwrap();
+    fs::write(dir.join("groups.yaml"), "upstairs: [\"Up*\"]\n").unwrap();
+
+    let office = location.with_profile(Some("office".to_string())).unwrap();
+    storage::create_config_dir(&office).unwrap();
+    storage::write_api_key(&office, "office-key".to_string()).unwrap();
+    storage::write_tokens(&office, &common::tokens("office-access", "office-refresh")).unwrap();
+
+    assert_eq!(fs::read_to_string(dir.join("profiles/office/api_key")).unwrap(), "office-key");
+    assert!(dir.join("profiles/office/api_tokens").exists());
+    assert!(!dir.join("api_tokens").exists());
+    assert_eq!(storage::load_app_key(&office).unwrap(), "office-key");
+    assert!(storage::load_groups(&office).unwrap().is_empty());
+    fs::write(dir.join("profiles/office/groups.yaml"), "floor: [\"Floor*\"]\n").unwrap();
+    assert!(storage::load_groups(&office).unwrap().contains_key("floor"));
+
+    assert_eq!(storage::load_app_key(&location).unwrap(), "default-key");
+    assert!(storage::load_groups(&location).unwrap().contains_key("upstairs"));
+    assert!(storage::load_tokens(&location).is_err());
+}
+
+#[test]
+fn list_profiles_finds_every_account_with_a_key() {
+    let (_guard, dir, location) = common::scratch_home("profiles");
+    storage::write_api_key(&location, "default-key".to_string()).unwrap();
+    for name in ["warehouse", "office"] {
+        let profile = location.with_profile(Some(name.to_string())).unwrap();
+        storage::create_config_dir(&profile).unwrap();
+        storage::write_api_key(&profile, format!("{name}-key")).unwrap();
+    }
+    fs::create_dir_all(dir.join("profiles/unfinished")).unwrap();
+
+    let profiles = storage::list_profiles(&location).unwrap();
+
+    assert_eq!(profiles, [None, Some("office".to_string()), Some("warehouse".to_string())]);
+}
+
+#[test]
+fn profile_names_are_validated() {
+    let (_guard, _, location) = common::scratch_home("profiles");
+
+    assert!(location.with_profile(Some("../elsewhere".to_string())).is_err());
+    assert!(location.with_profile(Some(String::new())).is_err());
+    let profile = location.with_profile(Some("building-2_east".to_string())).unwrap();
+    assert_eq!(profile.profile(), Some("building-2_east"));
+    assert_eq!(location.profile(), None);
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use common::{fast_retries, ok_status, status_body, tokens, MockResponse, MockServer};
+use serde_json::json;
+use std::time::Duration;
+
+use bulk_ecobee_thermostat_control::ecobee::models::{FanMode, ThermostatMeta, Vacation};
+use bulk_ecobee_thermostat_control::http::RetryPolicy;
+use bulk_ecobee_thermostat_control::{EcobeeClient, Error};
+
+fn upstairs() -> ThermostatMeta {
+    ThermostatMeta { identifier: "111".to_string(), name: "Upstairs".to_string() }
+}
+
+fn error_body() -> serde_json::Value {
+    json!({"status": {"code": 3, "message": "Processing error."}})
+}
+
+#[test]
+fn retries_server_errors_until_success() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(500, error_body()));
+    server.on("GET", "/1/thermostat", MockResponse::json(503, error_body()));
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "heat")])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    assert_eq!(client.thermostat_status().unwrap().thermostats.len(), 1);
+    assert_eq!(server.requests_to("GET", "/1/thermostat").len(), 3);
+}
+
+#[test]
+fn gives_up_after_configured_retries() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(500, error_body()));
+    let (client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    let mut client = client.with_retry_policy(fast_retries(2));
+
+    let report = client.resume_program(&[upstairs()], true).unwrap();
+
+    assert!(matches!(report.results[0].result, Err(Error::Status { code: 500, .. })));
+    assert_eq!(server.requests_to("POST", "/1/thermostat").len(), 3);
+}
+
+#[test]
+fn does_not_retry_client_errors() {
+    let server = MockServer::start();
+    server.on("POST", "/token", MockResponse::json(401, json!({"error": "invalid_grant"})));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    assert!(matches!(client.refresh_tokens(), Err(Error::Status { code: 401, .. })));
+    assert_eq!(server.requests_to("POST", "/token").len(), 1);
+}
+
+#[test]
+fn never_retries_token_requests() {
+    let server = MockServer::start();
+    server.on("POST", "/token", MockResponse::json(503, json!({"error": "unavailable"})));
+    server.on("POST", "/token", MockResponse::json(200, json!({
+        "access_token": "access-2", "token_type": "Bearer", "expires_in": 3600, "refresh_token": "refresh-2", "scope": "smartWrite"
+    })));
+    let (client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    let mut client = client.with_retry_policy(fast_retries(2));
+
+    assert!(matches!(client.refresh_tokens(), Err(Error::Status { code: 503, .. })));
+    assert_eq!(server.requests_to("POST", "/token").len(), 1);
+}
+
+#[test]
+fn honors_retry_after_for_too_many_requests() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(429, error_body()).with_header("Retry-After", "0"));
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    // A base delay this long would time the test out, so the zero Retry-After must have been used.
+    let mut client = client.with_retry_policy(RetryPolicy { retries: 1, base_delay: Duration::from_secs(600), max_delay: Duration::from_secs(600) });
+
+    client.resume_program(&[upstairs()], true).unwrap().into_result().unwrap();
+    assert_eq!(server.requests_to("POST", "/1/thermostat").len(), 2);
+}
+
+#[test]
+fn does_not_wait_for_retry_after_beyond_max_delay() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(429, error_body()).with_header("Retry-After", "3600"));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let report = client.resume_program(&[upstairs()], true).unwrap();
+
+    assert!(matches!(report.results[0].result, Err(Error::Status { code: 429, .. })));
+    assert_eq!(server.requests_to("POST", "/1/thermostat").len(), 1);
+}
+
+#[test]
+fn retries_transport_errors() {
+    // Bind then drop a listener to get a local port nothing is listening on.
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let store = common::MemoryTokenStore::new(Some(tokens("access-1", "refresh-1")));
+    let mut client = EcobeeClient::new("test-app-key".to_string(), Box::new(store))
+        .with_base_url(&format!("http://127.0.0.1:{port}"))
+        .with_retry_policy(fast_retries(2));
+
+    assert!(matches!(client.thermostat_status(), Err(Error::Transport(_))));
+}
+
+#[test]
+fn never_retries_create_vacation() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(503, error_body()));
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    let mut client = client.with_retry_policy(fast_retries(2));
+    let vacation = Vacation {
+        name: "Holidays".to_string(),
+        start: chrono::NaiveDate::from_ymd_opt(2026, 12, 20).unwrap().and_hms_opt(8, 0, 0).unwrap(),
+        end: chrono::NaiveDate::from_ymd_opt(2026, 12, 27).unwrap().and_hms_opt(18, 0, 0).unwrap(),
+        heat_hold_temp: 550,
+        cool_hold_temp: 850,
+        fan: FanMode::Auto,
+    };
+
+    let report = client.create_vacation(&[upstairs()], &vacation).unwrap();
+
+    assert!(matches!(report.results[0].result, Err(Error::Status { code: 503, .. })));
+    assert_eq!(server.requests_to("POST", "/1/thermostat").len(), 1);
+}
+
+#[test]
+fn never_retries_acknowledge() {
+    let server = MockServer::start();
+    server.on("POST", "/1/thermostat", MockResponse::json(503, error_body()));
+    server.on("POST", "/1/thermostat", MockResponse::json(200, ok_status()));
+    let (client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    let mut client = client.with_retry_policy(fast_retries(2));
+
+    let report = client.acknowledge_alert(&upstairs(), "111$1").unwrap();
+
+    assert!(matches!(report.results[0].result, Err(Error::Status { code: 503, .. })));
+    assert_eq!(server.requests_to("POST", "/1/thermostat").len(), 1);
+}
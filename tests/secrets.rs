@@ -0,0 +1,77 @@
+mod common;
+
+use std::fs;
+
+use bulk_ecobee_thermostat_control::secrets::{self, EncryptedFileSecretStore, PASSPHRASE_ENV, PASSPHRASE_FD_ENV};
+use bulk_ecobee_thermostat_control::{storage, Error};
+
+#[test]
+fn encrypted_secrets_round_trip() {
+    let store = EncryptedFileSecretStore::new("correct horse".to_string());
+
+    let document = store.encrypt("refresh-1").unwrap();
+
+    assert!(!document.contains("refresh-1"));
+    assert!(document.starts_with("version: 1\n"));
+    assert_eq!(store.decrypt(&document, "tokens").unwrap(), "refresh-1");
+    assert_ne!(store.encrypt("refresh-1").unwrap(), document);
+}
+
+#[test]
+fn wrong_passphrase_is_a_config_error() {
+    let document = EncryptedFileSecretStore::new("correct horse".to_string()).encrypt("refresh-1").unwrap();
+
+    let result = EncryptedFileSecretStore::new("battery staple".to_string()).decrypt(&document, "tokens");
+
+    assert!(matches!(result, Err(Error::Config(_))));
+}
+
+#[test]
+fn encrypted_backend_is_selected_by_config() {
+    let (_guard, dir, location) = common::scratch_home("secrets");
+    fs::write(dir.join("config.yaml"), "secrets: encrypted_file\n").unwrap();
+    fs::write(dir.join("api_key"), "app-key").unwrap();
+    std::env::set_var(PASSPHRASE_ENV, "correct horse");
+
+    storage::write_tokens(&location, &common::tokens("access-1", "refresh-1")).unwrap();
+
+    assert!(!dir.join("api_tokens").exists());
+    assert!(!fs::read_to_string(dir.join("api_tokens.enc")).unwrap().contains("refresh-1"));
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-1");
+
+    // A plain secret left from the file backend is encrypted on first read.
+    assert_eq!(storage::load_app_key(&location).unwrap(), "app-key");
+    assert!(!dir.join("api_key").exists());
+    assert!(dir.join("api_key.enc").exists());
+    assert_eq!(storage::load_app_key(&location).unwrap(), "app-key");
+}
+
+#[test]
+fn plain_tokens_file_is_only_encrypted_under_the_lock() {
+    let (_guard, dir, location) = common::scratch_home("secrets-lock");
+    fs::write(dir.join("config.yaml"), "secrets: encrypted_file\n").unwrap();
+    fs::write(dir.join("api_tokens"), "version: 1\naccess_token: access-1\nrefresh_token: refresh-1\n").unwrap();
+    std::env::set_var(PASSPHRASE_ENV, "correct horse");
+
+    let lock = storage::lock_tokens(&location).unwrap();
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-1");
+    assert!(dir.join("api_tokens").exists());
+    assert!(!dir.join("api_tokens.enc").exists());
+    drop(lock);
+
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-1");
+    assert!(!dir.join("api_tokens").exists());
+    assert!(dir.join("api_tokens.enc").exists());
+}
+
+#[test]
+fn passphrase_is_not_read_from_standard_output() {
+    let (_guard, _, _) = common::scratch_home("secrets-fd");
+    std::env::remove_var(PASSPHRASE_ENV);
+    std::env::set_var(PASSPHRASE_FD_ENV, "1");
+
+    let result = secrets::passphrase();
+    std::env::remove_var(PASSPHRASE_FD_ENV);
+
+    assert!(matches!(result, Err(Error::Config(_))));
+}
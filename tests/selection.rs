@@ -0,0 +1,58 @@
+use bulk_ecobee_thermostat_control::ecobee::models::ThermostatMeta;
+use bulk_ecobee_thermostat_control::selection::{Groups, Selection};
+use bulk_ecobee_thermostat_control::Error;
+
+fn thermostats() -> Vec<ThermostatMeta> {
+    [("111", "Upstairs Hall"), ("222", "Upstairs Bedroom"), ("333", "Kitchen"), ("444", "Basement")]
+        .iter()
+        .map(|(identifier, name)| ThermostatMeta { identifier: identifier.to_string(), name: name.to_string() })
+        .collect()
+}
+
+fn names(selected: Vec<ThermostatMeta>) -> Vec<String> {
+    selected.into_iter().map(|t| t.name).collect()
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+#[test]
+fn empty_selection_is_everything() {
+    let selected = Selection::default().resolve(&Groups::new()).unwrap().apply(thermostats()).unwrap();
+    assert_eq!(selected.len(), 4);
+}
+
+#[test]
+fn only_matches_globs_and_identifiers() {
+    let selection = Selection { only: strings(&["upstairs*", "333"]), ..Default::default() };
+    let selected = selection.resolve(&Groups::new()).unwrap().apply(thermostats()).unwrap();
+    assert_eq!(names(selected), ["Upstairs Hall", "Upstairs Bedroom", "Kitchen"]);
+}
+
+#[test]
+fn except_wins_over_only() {
+    let selection = Selection { only: strings(&["*"]), except: strings(&["Basement", "Upstairs ?all"]), ..Default::default() };
+    let selected = selection.resolve(&Groups::new()).unwrap().apply(thermostats()).unwrap();
+    assert_eq!(names(selected), ["Upstairs Bedroom", "Kitchen"]);
+}
+
+#[test]
+fn groups_expand_to_their_patterns() {
+    let groups = Groups::from([("main".to_string(), strings(&["*stairs*", "333"]))]);
+    let selection = Selection { groups: strings(&["main"]), except: strings(&["222"]), ..Default::default() };
+    let selected = selection.resolve(&groups).unwrap().apply(thermostats()).unwrap();
+    assert_eq!(names(selected), ["Upstairs Hall", "Kitchen"]);
+}
+
+#[test]
+fn unknown_group_is_an_error() {
+    let selection = Selection { groups: strings(&["attic"]), ..Default::default() };
+    assert!(matches!(selection.resolve(&Groups::new()), Err(Error::Config(_))));
+}
+
+#[test]
+fn selecting_nothing_is_an_error() {
+    let selection = Selection { only: strings(&["Garage"]), ..Default::default() };
+    assert!(matches!(selection.resolve(&Groups::new()).unwrap().apply(thermostats()), Err(Error::Config(_))));
+}
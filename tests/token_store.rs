@@ -0,0 +1,97 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::MutexGuard;
+use std::thread;
+
+use common::{expiring_tokens, MockResponse, MockServer};
+use serde_json::json;
+
+use bulk_ecobee_thermostat_control::storage::{self, FileTokenStore, Location};
+use bulk_ecobee_thermostat_control::{EcobeeClient, TokenStore};
+
+fn home() -> (MutexGuard<'static, ()>, PathBuf, Location) {
+    common::scratch_home("token_store")
+}
+
+#[test]
+fn tokens_file_is_versioned_yaml() {
+    let (_guard, dir, location) = home();
+    let tokens = expiring_tokens("access-1", "refresh-1", 3600);
+
+    storage::write_tokens(&location, &tokens).unwrap();
+
+    let content = fs::read_to_string(dir.join("api_tokens")).unwrap();
+    assert!(content.starts_with("version: 1\n"), "{content}");
+    let loaded = storage::load_tokens(&location).unwrap();
+    assert_eq!(loaded.access_token, "access-1");
+    assert_eq!(loaded.refresh_token, "refresh-1");
+    assert_eq!(loaded.expires_at, tokens.expires_at);
+    let leftovers: Vec<_> = fs::read_dir(&dir).unwrap().flatten()
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+        .collect();
+    assert!(leftovers.is_empty());
+}
+
+#[test]
+fn legacy_tokens_file_is_migrated() {
+    let (_guard, dir, location) = home();
+    fs::write(dir.join("api_tokens"), "access-1\nrefresh-1").unwrap();
+
+    let loaded = storage::load_tokens(&location).unwrap();
+
+    assert_eq!(loaded.access_token, "access-1");
+    assert_eq!(loaded.refresh_token, "refresh-1");
+    assert!(loaded.expires_at.is_none());
+    assert!(fs::read_to_string(dir.join("api_tokens")).unwrap().starts_with("version: 1\n"));
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-1");
+}
+
+#[test]
+fn legacy_tokens_file_is_only_migrated_under_the_lock() {
+    let (_guard, dir, location) = home();
+    fs::write(dir.join("api_tokens"), "access-1\nrefresh-1").unwrap();
+
+    let lock = storage::lock_tokens(&location).unwrap();
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-1");
+    assert_eq!(fs::read_to_string(dir.join("api_tokens")).unwrap(), "access-1\nrefresh-1");
+    drop(lock);
+
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-1");
+    assert!(fs::read_to_string(dir.join("api_tokens")).unwrap().starts_with("version: 1\n"));
+}
+
+#[test]
+fn newer_tokens_file_version_is_rejected() {
+    let (_guard, dir, location) = home();
+    fs::write(dir.join("api_tokens"), "version: 2\naccess_token: a\nrefresh_token: r\n").unwrap();
+
+    assert!(storage::load_tokens(&location).is_err());
+}
+
+#[test]
+fn concurrent_refreshes_spend_the_refresh_token_once() {
+    let (_guard, _, location) = home();
+    let server = MockServer::start();
+    server.on("POST", "/token", MockResponse::json(200, json!({
+        "access_token": "access-2", "token_type": "Bearer", "expires_in": 3599, "refresh_token": "refresh-2", "scope": "smartWrite"
+    })));
+    FileTokenStore::new(location.clone()).save(&expiring_tokens("access-1", "refresh-1", -60)).unwrap();
+
+    let workers: Vec<_> = (0..4).map(|_| {
+        let (url, location) = (server.url(), location.clone());
+        thread::spawn(move || {
+            let mut client = EcobeeClient::new("test-app-key".to_string(), Box::new(FileTokenStore::new(location)))
+                .with_base_url(&url)
+                .with_retry_policy(common::fast_retries(0));
+            client.valid_access_token().unwrap()
+        })
+    }).collect();
+
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), "access-2");
+    }
+    assert_eq!(server.requests_to("POST", "/token").len(), 1);
+    assert_eq!(storage::load_tokens(&location).unwrap().refresh_token, "refresh-2");
+}
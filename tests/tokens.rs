@@ -0,0 +1,93 @@
+mod common;
+
+use common::{expiring_tokens, status_body, tokens, MockResponse, MockServer};
+use serde_json::json;
+
+fn token_response(access: &str, refresh: &str) -> MockResponse {
+    MockResponse::json(200, json!({
+        "access_token": access,
+        "token_type": "Bearer",
+        "expires_in": 3599,
+        "refresh_token": refresh,
+        "scope": "smartWrite"
+    }))
+}
+
+fn expired_token() -> MockResponse {
+    MockResponse::json(500, json!({"status": {"code": 14, "message": "Authentication token has expired. Refresh your tokens."}}))
+}
+
+#[test]
+fn valid_token_is_not_refreshed() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "heat")])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    client.thermostat_status().unwrap();
+    client.thermostat_status().unwrap();
+
+    assert!(server.requests_to("POST", "/token").is_empty());
+    assert_eq!(server.requests_to("GET", "/1/thermostat")[1].headers["authorization"], "Bearer access-1");
+}
+
+#[test]
+fn token_near_expiry_is_refreshed_first() {
+    let server = MockServer::start();
+    server.on("POST", "/token", token_response("access-2", "refresh-2"));
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "heat")])));
+    let (mut client, store) = server.client(Some(expiring_tokens("access-1", "refresh-1", 60)));
+
+    client.thermostat_status().unwrap();
+
+    assert_eq!(server.requests_to("POST", "/token").len(), 1);
+    assert_eq!(server.requests_to("GET", "/1/thermostat")[0].headers["authorization"], "Bearer access-2");
+    let saved = store.saved().unwrap();
+    assert_eq!(saved.refresh_token, "refresh-2");
+    let lifetime = saved.expires_at.unwrap() - saved.issued_at.unwrap();
+    assert_eq!(lifetime.num_seconds(), 3599);
+    assert!(!saved.expires_within(chrono::Duration::minutes(5)));
+}
+
+#[test]
+fn unknown_expiry_is_refreshed() {
+    let server = MockServer::start();
+    server.on("POST", "/token", token_response("access-2", "refresh-2"));
+    let mut legacy = tokens("access-1", "refresh-1");
+    legacy.issued_at = None;
+    legacy.expires_at = None;
+    let (mut client, _) = server.client(Some(legacy));
+
+    assert_eq!(client.valid_access_token().unwrap(), "access-2");
+    assert_eq!(client.valid_access_token().unwrap(), "access-2");
+    assert_eq!(server.requests_to("POST", "/token").len(), 1);
+}
+
+#[test]
+fn expired_token_error_refreshes_and_retries_once() {
+    let server = MockServer::start();
+    server.on("POST", "/token", token_response("access-2", "refresh-2"));
+    server.on("GET", "/1/thermostat", expired_token());
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "heat")])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    client.thermostat_status().unwrap();
+
+    let requests = server.requests_to("GET", "/1/thermostat");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].headers["authorization"], "Bearer access-1");
+    assert_eq!(requests[1].headers["authorization"], "Bearer access-2");
+    assert_eq!(server.requests_to("POST", "/token").len(), 1);
+}
+
+#[test]
+fn expired_token_error_twice_is_surfaced() {
+    let server = MockServer::start();
+    server.on("POST", "/token", token_response("access-2", "refresh-2"));
+    server.on("GET", "/1/thermostat", expired_token());
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let error = client.thermostat_status().unwrap_err();
+
+    assert!(error.is_token_expired());
+    assert_eq!(server.requests_to("GET", "/1/thermostat").len(), 2);
+}
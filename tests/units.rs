@@ -0,0 +1,23 @@
+use bulk_ecobee_thermostat_control::units::TemperatureUnit;
+
+#[test]
+fn converts_to_ecobee_tenths_of_fahrenheit() {
+    assert_eq!(TemperatureUnit::Fahrenheit.to_ecobee(68.0), 680);
+    assert_eq!(TemperatureUnit::Fahrenheit.to_ecobee(71.55), 716);
+    assert_eq!(TemperatureUnit::Celsius.to_ecobee(20.0), 680);
+    assert_eq!(TemperatureUnit::Celsius.to_ecobee(-40.0), -400);
+}
+
+#[test]
+fn converts_from_ecobee_tenths_of_fahrenheit() {
+    assert_eq!(TemperatureUnit::Fahrenheit.from_ecobee(715.0), 71.5);
+    assert_eq!(TemperatureUnit::Celsius.from_ecobee(680.0), 20.0);
+    assert_eq!(TemperatureUnit::Celsius.from_ecobee(715.0), 21.9);
+}
+
+#[test]
+fn metric_setting_selects_celsius() {
+    assert_eq!(TemperatureUnit::from_metric(Some(true)), TemperatureUnit::Celsius);
+    assert_eq!(TemperatureUnit::from_metric(Some(false)), TemperatureUnit::Fahrenheit);
+    assert_eq!(TemperatureUnit::from_metric(None), TemperatureUnit::Fahrenheit);
+}
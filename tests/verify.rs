@@ -0,0 +1,70 @@
+mod common;
+
+use common::{status_body, summary_body, tokens, MockResponse, MockServer};
+use std::time::Duration;
+
+use bulk_ecobee_thermostat_control::ecobee::models::{HvacMode, ThermostatMeta};
+use bulk_ecobee_thermostat_control::ecobee::verify::VerifyOptions;
+
+fn metas() -> Vec<ThermostatMeta> {
+    [("111", "Upstairs"), ("222", "Downstairs")]
+        .iter()
+        .map(|(identifier, name)| ThermostatMeta { identifier: identifier.to_string(), name: name.to_string() })
+        .collect()
+}
+
+fn options(timeout_ms: u64) -> VerifyOptions {
+    VerifyOptions {
+        timeout: Duration::from_millis(timeout_ms),
+        initial_delay: Duration::from_millis(5),
+        max_delay: Duration::from_millis(20),
+    }
+}
+
+#[test]
+fn polls_until_every_thermostat_converges() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "cool"), ("222", "Downstairs", "heat")])));
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("222", "Downstairs", "cool")])));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary_body(&[("111", "Upstairs", "1"), ("222", "Downstairs", "1")])));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary_body(&[("111", "Upstairs", "1"), ("222", "Downstairs", "2")])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let unconverged = client.verify_hvac_mode(&metas(), HvacMode::Cool, &options(5_000)).unwrap();
+
+    assert!(unconverged.is_empty());
+    let polls = server.requests_to("GET", "/1/thermostat");
+    assert_eq!(polls.len(), 2);
+    assert_eq!(polls[0].json_query("json")["selection"]["selectionMatch"], "111,222");
+    // Upstairs converged on the first poll, so only Downstairs is asked about again.
+    assert_eq!(polls[1].json_query("json")["selection"]["selectionMatch"], "222");
+}
+
+#[test]
+fn reports_thermostats_that_never_converge() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "off"), ("222", "Downstairs", "heat")])));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary_body(&[("111", "Upstairs", "1"), ("222", "Downstairs", "1")])));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary_body(&[("111", "Upstairs", "1"), ("222", "Downstairs", "2")])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let unconverged = client.verify_hvac_mode(&metas(), HvacMode::Off, &options(60)).unwrap();
+
+    assert_eq!(unconverged.len(), 1);
+    assert_eq!(unconverged[0].name, "Downstairs");
+    assert!(server.requests_to("GET", "/1/thermostat").len() >= 2);
+}
+
+#[test]
+fn only_reads_back_modes_when_revisions_change() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "heat"), ("222", "Downstairs", "heat")])));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary_body(&[("111", "Upstairs", "1"), ("222", "Downstairs", "1")])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+
+    let unconverged = client.verify_hvac_mode(&metas(), HvacMode::Cool, &options(80)).unwrap();
+
+    assert_eq!(unconverged.len(), 2);
+    assert!(server.requests_to("GET", "/1/thermostatSummary").len() >= 2);
+    assert_eq!(server.requests_to("GET", "/1/thermostat").len(), 1);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{status_body, tokens, MockResponse, MockServer};
+use serde_json::json;
+
+use bulk_ecobee_thermostat_control::config::Config;
+use bulk_ecobee_thermostat_control::ecobee::models::{HvacMode, HvacModeStatus, ThermostatMeta};
+use bulk_ecobee_thermostat_control::units::TemperatureUnit;
+use bulk_ecobee_thermostat_control::ecobee::models::SensorReading;
+use bulk_ecobee_thermostat_control::weather::api::{decide, get_temp, refresh, target_mode};
+use bulk_ecobee_thermostat_control::weather::models::{Conditions, WeatherSettings};
+use bulk_ecobee_thermostat_control::Error;
+
+fn settings(metric: bool) -> WeatherSettings {
+    WeatherSettings {
+        api_key: Some("weather-key".to_string()),
+        query: Some("11102".to_string()),
+        metric: Some(metric),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn get_temp_uses_configured_units() {
+    let server = MockServer::start();
+    server.on("POST", "/v1/current.json", MockResponse::json(200, json!({"current": {"temp_c": 21.5, "temp_f": 70.7}})));
+
+    assert_eq!(get_temp(&settings(false), &server.config()).unwrap(), 70.7);
+    assert_eq!(get_temp(&settings(true), &server.config()).unwrap(), 21.5);
+
+    let request = &server.requests_to("POST", "/v1/current.json")[0];
+    assert_eq!(request.query["key"], "weather-key");
+    assert_eq!(request.query["q"], "11102");
+}
+
+#[test]
+fn get_temp_uses_the_units_setting_over_metric() {
+    let server = MockServer::start();
+    server.on("POST", "/v1/current.json", MockResponse::json(200, json!({"current": {"temp_c": 21.5, "temp_f": 70.7}})));
+    let config = Config { units: Some(TemperatureUnit::Celsius), ..server.config() };
+
+    assert_eq!(get_temp(&settings(false), &config).unwrap(), 21.5);
+}
+
+#[test]
+fn get_temp_without_temperature_is_a_json_error() {
+    let server = MockServer::start();
+    server.on("POST", "/v1/current.json", MockResponse::json(200, json!({"current": {}})));
+
+    assert!(matches!(get_temp(&settings(false), &server.config()), Err(Error::Json(_))));
+}
+
+#[test]
+fn get_temp_requires_settings() {
+    let server = MockServer::start();
+
+    assert!(matches!(get_temp(&WeatherSettings::default(), &server.config()), Err(Error::Config(_))));
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn target_mode_prefers_cool_and_heat_over_off() {
+    let settings = WeatherSettings {
+        cool_above: Some(80.0),
+        off_above: Some(70.0),
+        off_below: Some(50.0),
+        heat_below: Some(40.0),
+        ..Default::default()
+    };
+
+    assert_eq!(target_mode(&settings, 85.0), Some(HvacMode::Cool));
+    assert_eq!(target_mode(&settings, 75.0), Some(HvacMode::Off));
+    assert_eq!(target_mode(&settings, 60.0), None);
+    assert_eq!(target_mode(&settings, 45.0), Some(HvacMode::Off));
+    assert_eq!(target_mode(&settings, 35.0), Some(HvacMode::Heat));
+}
+
+#[test]
+fn occupied_only_holds_changes_back_while_sensors_report_nobody() {
+    let settings = WeatherSettings { cool_above: Some(80.0), heat_below: Some(40.0), occupied_only: Some(true), ..Default::default() };
+    let sensor = |occupied: Option<bool>| SensorReading { name: "Bedroom".to_string(), temperature: Some(70.0), humidity: None, occupied };
+    let conditions = |sensors: Vec<SensorReading>| Conditions { outdoor: 85.0, sensors };
+
+    assert_eq!(decide(&settings, &conditions(vec![sensor(Some(false)), sensor(Some(false))])), None);
+    assert_eq!(decide(&settings, &conditions(vec![sensor(Some(false)), sensor(Some(true))])), Some(HvacMode::Cool));
+    assert_eq!(decide(&settings, &conditions(vec![sensor(None)])), Some(HvacMode::Cool));
+    assert_eq!(decide(&settings, &conditions(vec![])), Some(HvacMode::Cool));
+
+    let anyone = WeatherSettings { occupied_only: None, ..settings };
+    assert_eq!(decide(&anyone, &conditions(vec![sensor(Some(false))])), Some(HvacMode::Cool));
+}
+
+#[test]
+fn get_temp_retries_server_errors() {
+    let server = MockServer::start();
+    server.on("POST", "/v1/current.json", MockResponse::json(502, json!({"error": "bad gateway"})));
+    server.on("POST", "/v1/current.json", MockResponse::json(200, json!({"current": {"temp_c": 21.5, "temp_f": 70.7}})));
+
+    assert_eq!(get_temp(&settings(false), &server.config()).unwrap(), 70.7);
+    assert_eq!(server.requests_to("POST", "/v1/current.json").len(), 2);
+}
+
+/// A summary where thermostat 111 has the given thermostat and runtime revisions.
+fn summary(thermostat_revision: &str, runtime_revision: &str) -> serde_json::Value {
+    json!({
+        "revisionList": [format!("111:Upstairs:true:{thermostat_revision}:alerts:{runtime_revision}:interval")],
+        "statusList": ["111:"],
+        "status": {"code": 0, "message": ""}
+    })
+}
+
+#[test]
+fn refresh_only_follows_runtime_revisions_for_occupied_only() {
+    let server = MockServer::start();
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary("t1", "r1")));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary("t1", "r2")));
+    server.on("GET", "/1/thermostatSummary", MockResponse::json(200, summary("t1", "r3")));
+    server.on("GET", "/1/thermostat", MockResponse::json(200, status_body(&[("111", "Upstairs", "cool")])));
+    let (mut client, _) = server.client(Some(tokens("access-1", "refresh-1")));
+    let thermostats = vec![ThermostatMeta { identifier: "111".to_string(), name: "Upstairs".to_string() }];
+    let mut summary = client.thermostat_summary().unwrap();
+
+    assert!(refresh(&mut client, &thermostats, &mut summary, TemperatureUnit::Fahrenheit, false).unwrap().is_none());
+    assert!(server.requests_to("GET", "/1/thermostat").is_empty());
+
+    let (modes, _) = refresh(&mut client, &thermostats, &mut summary, TemperatureUnit::Fahrenheit, true).unwrap().unwrap();
+    assert_eq!(modes, HvacModeStatus::Uniform(HvacMode::Cool));
+    let selection = &server.requests_to("GET", "/1/thermostat")[0].json_query("json")["selection"];
+    assert_eq!(selection["includeSensors"], true);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::fs;
+use std::io::Cursor;
+
+use bulk_ecobee_thermostat_control::ecobee::api::{api_key_from, set_api_key};
+use bulk_ecobee_thermostat_control::prompt::read_line_from;
+use bulk_ecobee_thermostat_control::weather::models::WeatherSettings;
+use bulk_ecobee_thermostat_control::weather::settings::{self, validate};
+use bulk_ecobee_thermostat_control::{storage, Error};
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+fn valid() -> WeatherSettings {
+    WeatherSettings {
+        api_key: Some("weather-key".to_string()),
+        query: Some("11102".to_string()),
+        cool_above: Some(80.0),
+        off_above: Some(70.0),
+        off_below: Some(50.0),
+        heat_below: Some(40.0),
+        interval: Some(30),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn validate_reports_every_problem_without_panicking() {
+    assert!(validate(&valid()).is_ok());
+
+    match validate(&WeatherSettings::default()) {
+        Err(Error::Config(message)) => {
+            for problem in ["API Key unset.", "Query unset.", "Interval unset.", "Cool Above unset.", "Heat Below unset."] {
+                assert!(message.contains(problem), "{message}");
+            }
+        }
+        other => panic!("expected a config error, got {other:?}"),
+    }
+
+    let out_of_order = WeatherSettings { off_below: Some(75.0), ..valid() };
+    assert!(validate(&out_of_order).is_err());
+    let no_interval = WeatherSettings { interval: Some(0), ..valid() };
+    assert!(validate(&no_interval).is_err());
+}
+
+#[test]
+fn set_changes_only_the_named_settings() {
+    let (_guard, _, location) = common::scratch_home("weather_settings");
+    storage::write_weather_settings(&location, valid()).unwrap();
+
+    settings::set(&location, &strings(&["interval=15", "off_below=", "metric=true"])).unwrap();
+
+    let saved = storage::load_weather_settings(&location).unwrap();
+    assert_eq!(saved.interval, Some(15));
+    assert_eq!(saved.off_below, None);
+    assert_eq!(saved.metric, Some(true));
+    assert_eq!(saved.api_key.as_deref(), Some("weather-key"));
+}
+
+#[test]
+fn set_rejects_bad_input_and_keeps_the_old_settings() {
+    let (_guard, _, location) = common::scratch_home("weather_settings");
+    storage::write_weather_settings(&location, valid()).unwrap();
+
+    assert!(settings::set(&location, &strings(&["colour=blue"])).is_err());
+    assert!(settings::set(&location, &strings(&["interval"])).is_err());
+    assert!(settings::set(&location, &strings(&["interval=soon"])).is_err());
+    assert!(settings::set(&location, &strings(&["cool_above=45"])).is_err());
+
+    assert_eq!(storage::load_weather_settings(&location).unwrap().cool_above, Some(80.0));
+}
+
+#[test]
+fn import_validates_and_saves_a_yaml_file() {
+    let (_guard, dir, location) = common::scratch_home("weather_settings");
+    let file = dir.join("provisioned.yaml");
+    fs::write(&file, serde_yaml::to_string(&valid()).unwrap()).unwrap();
+
+    settings::import(&location, file.to_str().unwrap()).unwrap();
+
+    assert_eq!(storage::load_weather_settings(&location).unwrap().query.as_deref(), Some("11102"));
+    fs::write(&file, "api_key: only-a-key\n").unwrap();
+    assert!(settings::import(&location, file.to_str().unwrap()).is_err());
+}
+
+#[test]
+fn api_key_is_stored_trimmed_and_must_not_be_empty() {
+    let (_guard, dir, location) = common::scratch_home("weather_settings");
+
+    api_key_from(&location, Cursor::new("  app-key\n")).unwrap();
+    assert_eq!(fs::read_to_string(dir.join("api_key")).unwrap(), "app-key");
+
+    assert!(matches!(api_key_from(&location, Cursor::new("")), Err(Error::Config(_))));
+    assert!(matches!(set_api_key(&location, "  "), Err(Error::Config(_))));
+    assert_eq!(storage::load_app_key(&location).unwrap(), "app-key");
+}
+
+#[test]
+fn read_line_errors_at_end_of_input() {
+    let mut input = Cursor::new("first\r\n\n");
+
+    assert_eq!(read_line_from(&mut input).unwrap(), "first");
+    assert_eq!(read_line_from(&mut input).unwrap(), "");
+    assert!(matches!(read_line_from(&mut input), Err(Error::Config(_))));
+}